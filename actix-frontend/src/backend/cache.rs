@@ -0,0 +1,294 @@
+use super::{
+    CountChunksPayload, GroupSearchPayload, GroupSearchResponse, RecommendChunksPayload,
+    ScrollChunksPayload, SearchBackend,
+};
+use crate::{
+    errors::SearchError,
+    handlers::search_handler::{
+        CustomSearchChunksReqPayload, ScoreChunkMetadata, SimplifiedSearchResponse,
+    },
+};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use trieve_client::models::ChunkMetadata;
+
+/// Where cached search responses are kept, serialized as JSON.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Option<String>;
+
+    async fn set(&self, key: &str, value: String);
+}
+
+/// Keeps up to `capacity` responses for `ttl` in the process, evicting the least recently
+/// used one when full.
+pub struct MemoryCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<LruEntries>,
+}
+
+#[derive(Default)]
+struct LruEntries {
+    entries: HashMap<String, CacheEntry>,
+    /// Incremented on each access, so the entry with the lowest `last_used` is the least
+    /// recently used one.
+    clock: u64,
+}
+
+struct CacheEntry {
+    value: String,
+    expires_at: Instant,
+    last_used: u64,
+}
+
+impl MemoryCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        MemoryCache {
+            capacity,
+            ttl,
+            entries: Mutex::new(LruEntries::default()),
+        }
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn get(&self, key: &str) -> Option<String> {
+        let mut lru = self.entries.lock().unwrap();
+        lru.clock += 1;
+        let clock = lru.clock;
+        match lru.entries.get_mut(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                entry.last_used = clock;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                lru.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    async fn set(&self, key: &str, value: String) {
+        let mut lru = self.entries.lock().unwrap();
+        lru.clock += 1;
+        let now = Instant::now();
+        if !lru.entries.contains_key(key) && lru.entries.len() >= self.capacity {
+            lru.entries.retain(|_, entry| entry.expires_at > now);
+        }
+        if !lru.entries.contains_key(key) && lru.entries.len() >= self.capacity {
+            let oldest = lru
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                lru.entries.remove(&oldest);
+            }
+        }
+        let entry = CacheEntry {
+            value,
+            expires_at: now + self.ttl,
+            last_used: lru.clock,
+        };
+        lru.entries.insert(key.to_string(), entry);
+    }
+}
+
+/// Shares cached responses between server processes through the Redis used by the ingest.
+#[cfg(feature = "redis")]
+pub struct RedisCache {
+    connection: redis::aio::ConnectionManager,
+    ttl: Duration,
+}
+
+#[cfg(feature = "redis")]
+impl RedisCache {
+    pub async fn connect(redis_url: &str, ttl: Duration) -> Result<Self, redis::RedisError> {
+        let client = redis::Client::open(redis_url)?;
+        let connection = redis::aio::ConnectionManager::new(client).await?;
+        Ok(RedisCache { connection, ttl })
+    }
+}
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl CacheStore for RedisCache {
+    async fn get(&self, key: &str) -> Option<String> {
+        let mut connection = self.connection.clone();
+        redis::cmd("GET")
+            .arg(key)
+            .query_async::<_, Option<String>>(&mut connection)
+            .await
+            .inspect_err(|err| tracing::warn!("Could not read {} from Redis: {}", key, err))
+            .ok()
+            .flatten()
+    }
+
+    async fn set(&self, key: &str, value: String) {
+        let mut connection = self.connection.clone();
+        let result = redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("PX")
+            .arg(self.ttl.as_millis() as u64)
+            .query_async::<_, ()>(&mut connection)
+            .await;
+        if let Err(err) = result {
+            tracing::warn!("Could not write {} to Redis: {}", key, err);
+        }
+    }
+}
+
+/// How often searches, scrolls and counts were answered from the cache.
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheStatsSnapshot {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    pub fn snapshot(&self) -> CacheStatsSnapshot {
+        CacheStatsSnapshot {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Starts every cache key, so the keys of this app stand out in the Redis it shares with the
+/// ingest. Bump the version when the cached payloads or responses change shape.
+pub const CACHE_KEY_PREFIX: &str = "hn-discovery:v1";
+
+/// Answers searches, scroll pages and counts from `store` when the same payload was sent recently,
+/// and passes everything else through to `inner`. Errors aren't cached.
+pub struct CachedBackend {
+    inner: Arc<dyn SearchBackend>,
+    store: Box<dyn CacheStore>,
+    stats: Arc<CacheStats>,
+    /// Identifies the data `inner` serves, so frontends of other datasets sharing `store`
+    /// don't answer from each other's responses.
+    dataset: String,
+}
+
+impl CachedBackend {
+    pub fn new(
+        inner: Arc<dyn SearchBackend>,
+        store: Box<dyn CacheStore>,
+        stats: Arc<CacheStats>,
+        dataset: &str,
+    ) -> Self {
+        CachedBackend {
+            inner,
+            store,
+            stats,
+            dataset: dataset.to_string(),
+        }
+    }
+
+    /// The cache key of the `kind` request sent with `payload`. Search payloads are built from
+    /// canonical search requests with relative dates counted from the start of the cache
+    /// period, so the same search serializes the same way for the whole period.
+    pub fn cache_key(&self, kind: &str, payload: &impl Serialize) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            CACHE_KEY_PREFIX,
+            self.dataset,
+            kind,
+            serde_json::to_string(payload).unwrap()
+        )
+    }
+
+    /// The value cached under `key`, or else the one `fetch` returns, which is cached.
+    async fn cached<T, F>(&self, key: &str, fetch: F) -> Result<T, SearchError>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T, SearchError>>,
+    {
+        let cached = self
+            .store
+            .get(key)
+            .await
+            .and_then(|value| serde_json::from_str(&value).ok());
+        if let Some(value) = cached {
+            self.stats.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+
+        self.stats.misses.fetch_add(1, Ordering::Relaxed);
+        let value = fetch.await?;
+        self.store
+            .set(key, serde_json::to_string(&value).unwrap())
+            .await;
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl SearchBackend for CachedBackend {
+    async fn search(
+        &self,
+        payload: &CustomSearchChunksReqPayload,
+    ) -> Result<SimplifiedSearchResponse, SearchError> {
+        self.cached(
+            &self.cache_key("search", payload),
+            self.inner.search(payload),
+        )
+        .await
+    }
+
+    async fn get_chunk(&self, tracking_id: &str) -> Result<Option<ChunkMetadata>, SearchError> {
+        self.inner.get_chunk(tracking_id).await
+    }
+
+    async fn get_chunks(&self, tracking_ids: &[String]) -> Result<Vec<ChunkMetadata>, SearchError> {
+        self.inner.get_chunks(tracking_ids).await
+    }
+
+    async fn recommend(
+        &self,
+        payload: &RecommendChunksPayload,
+    ) -> Result<Vec<ScoreChunkMetadata>, SearchError> {
+        self.inner.recommend(payload).await
+    }
+
+    async fn group_search(
+        &self,
+        payload: &GroupSearchPayload,
+    ) -> Result<GroupSearchResponse, SearchError> {
+        self.inner.group_search(payload).await
+    }
+
+    async fn scroll(
+        &self,
+        payload: &ScrollChunksPayload,
+    ) -> Result<Vec<ChunkMetadata>, SearchError> {
+        self.cached(
+            &self.cache_key("scroll", payload),
+            self.inner.scroll(payload),
+        )
+        .await
+    }
+
+    async fn count(&self, payload: &CountChunksPayload) -> Result<usize, SearchError> {
+        self.cached(&self.cache_key("count", payload), self.inner.count(payload))
+            .await
+    }
+}
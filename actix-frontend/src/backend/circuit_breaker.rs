@@ -0,0 +1,75 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Stops calling Trieve for a while after it failed `threshold` calls in a row, so requests
+/// fail fast instead of each waiting for their timeouts.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// When the call let through to probe Trieve after the cooldown started.
+    probe_started: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Whether a call may be made. Once the cooldown is over a single call is let through
+    /// to probe Trieve, and the others keep failing fast until it succeeds. A probe which
+    /// never reports back, such as one whose request was dropped, is replaced after another
+    /// cooldown.
+    pub fn allows_call(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match state.open_until {
+            None => true,
+            Some(open_until) if now < open_until => false,
+            Some(_) => {
+                let probing = state
+                    .probe_started
+                    .is_some_and(|probe_started| now < probe_started + self.cooldown);
+                if !probing {
+                    state.probe_started = Some(now);
+                }
+                !probing
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.probe_started = None;
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if state.consecutive_failures >= self.threshold || state.open_until.is_some() {
+            if state.open_until.is_none() {
+                tracing::warn!(
+                    "Opening the circuit breaker after {} failed Trieve calls",
+                    state.consecutive_failures
+                );
+            }
+            state.open_until = Some(Instant::now() + self.cooldown);
+            state.probe_started = None;
+        }
+    }
+}
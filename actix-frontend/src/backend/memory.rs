@@ -0,0 +1,657 @@
+use super::{
+    CountChunksPayload, GroupInfo, GroupResult, GroupSearchPayload, GroupSearchResponse,
+    RecommendChunksPayload, ScrollChunksPayload, SearchBackend,
+};
+use crate::{
+    errors::SearchError,
+    handlers::search_handler::{
+        CustomChunkFilter, CustomConditionType, CustomSearchChunksReqPayload, ScoreChunkMetadata,
+        SimplifiedSearchResponse,
+    },
+    query::ast::{Predicate, TextField},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+use trieve_client::models::{
+    self, ChunkMetadata, FieldCondition, MatchCondition, QdrantSortBy, SortOrder,
+};
+use uuid::Uuid;
+
+/// An item as served by the HN API, which is what fixture files hold.
+#[derive(Clone, Debug, Deserialize)]
+pub struct HnItem {
+    pub id: i64,
+    #[serde(rename = "type")]
+    pub item_type: String,
+    #[serde(default)]
+    pub by: String,
+    #[serde(default)]
+    pub time: i64,
+    pub title: Option<String>,
+    pub text: Option<String>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub score: i64,
+    pub descendants: Option<i64>,
+    pub parent: Option<i64>,
+    #[serde(default)]
+    pub kids: Vec<i64>,
+}
+
+/// Searches a fixed set of HN items in memory, turned into chunks the same way the ingest
+/// does. Every search type is a case-insensitive keyword match, scored by how often the
+/// query's words appear; score thresholds and highlights are not applied.
+pub struct InMemoryBackend {
+    chunks: Vec<ChunkMetadata>,
+}
+
+/// The text of a query split the way Trieve reads it with `use_quote_negated_terms`.
+#[derive(Default)]
+struct QueryTerms {
+    words: Vec<String>,
+    phrases: Vec<String>,
+    negated: Vec<String>,
+}
+
+impl QueryTerms {
+    fn parse(query: &str) -> QueryTerms {
+        let mut terms = QueryTerms::default();
+        let mut rest = query.to_lowercase();
+        while let Some(start) = rest.find('"') {
+            let Some(len) = rest[start + 1..].find('"') else {
+                break;
+            };
+            let phrase = rest[start + 1..start + 1 + len].trim().to_string();
+            let negated = rest[..start].ends_with('-');
+            let prefix_end = if negated { start - 1 } else { start };
+            if !phrase.is_empty() {
+                match negated {
+                    true => terms.negated.push(phrase),
+                    false => terms.phrases.push(phrase),
+                }
+            }
+            rest = format!("{} {}", &rest[..prefix_end], &rest[start + len + 2..]);
+        }
+        for word in rest.split_whitespace() {
+            match word.strip_prefix('-') {
+                Some(negated) if !negated.is_empty() => terms.negated.push(negated.to_string()),
+                _ => terms.words.push(word.to_string()),
+            }
+        }
+        terms
+    }
+
+    /// Score of a chunk, `None` when it doesn't match the query.
+    fn score(&self, chunk: &ChunkMetadata) -> Option<f32> {
+        let haystack = searchable_text(chunk);
+        if self
+            .negated
+            .iter()
+            .any(|term| haystack.contains(term.as_str()))
+            || !self
+                .phrases
+                .iter()
+                .all(|phrase| haystack.contains(phrase.as_str()))
+        {
+            return None;
+        }
+        if self.words.is_empty() {
+            return Some(1.0 + self.phrases.len() as f32);
+        }
+        let hits = self
+            .words
+            .iter()
+            .map(|word| haystack.matches(word.as_str()).count())
+            .sum::<usize>();
+        (hits > 0).then_some(hits as f32 + 2.0 * self.phrases.len() as f32)
+    }
+}
+
+fn searchable_text(chunk: &ChunkMetadata) -> String {
+    let metadata = chunk.metadata.clone().flatten().unwrap_or_default();
+    format!(
+        "{} {}",
+        chunk.chunk_html.clone().flatten().unwrap_or_default(),
+        metadata
+            .get("url")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    )
+    .to_lowercase()
+}
+
+fn parse_time_stamp(time_stamp: &str) -> Option<i64> {
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time_stamp, format).ok())
+        .map(|time| time.and_utc().timestamp())
+}
+
+/// Values of `field` on the chunk, as Trieve sees them when filtering and sorting.
+fn field_values(field: &str, chunk: &ChunkMetadata) -> Vec<Value> {
+    match field {
+        "tag_set" => chunk
+            .tag_set
+            .clone()
+            .flatten()
+            .unwrap_or_default()
+            .into_iter()
+            .map(Value::String)
+            .collect(),
+        "num_value" => chunk
+            .num_value
+            .flatten()
+            .map(|value| json!(value))
+            .into_iter()
+            .collect(),
+        "time_stamp" => chunk
+            .time_stamp
+            .clone()
+            .flatten()
+            .and_then(|time_stamp| parse_time_stamp(&time_stamp))
+            .map(|timestamp| json!(timestamp))
+            .into_iter()
+            .collect(),
+        field => match field.strip_prefix("metadata.") {
+            Some(key) => match chunk
+                .metadata
+                .clone()
+                .flatten()
+                .and_then(|metadata| metadata.get(key).cloned())
+            {
+                Some(Value::Array(values)) => values,
+                Some(Value::Null) | None => vec![],
+                Some(value) => vec![value],
+            },
+            None => vec![],
+        },
+    }
+}
+
+fn value_matches(field: &str, value: &Value, condition: &MatchCondition) -> bool {
+    match (value, condition) {
+        (Value::String(value), MatchCondition::String(condition)) if field == "tag_set" => {
+            value == condition
+        }
+        (Value::String(value), MatchCondition::String(condition)) => value
+            .to_lowercase()
+            .contains(condition.to_lowercase().as_str()),
+        (Value::Number(value), MatchCondition::Integer(condition)) => {
+            value.as_f64() == Some(*condition as f64)
+        }
+        (Value::Number(value), MatchCondition::Number(condition)) => {
+            value.as_f64() == Some(*condition)
+        }
+        _ => false,
+    }
+}
+
+fn range_value(condition: &Option<Option<Box<models::RangeCondition>>>) -> Option<f64> {
+    match condition.clone().flatten().as_deref() {
+        Some(models::RangeCondition::Integer(value)) => Some(*value as f64),
+        Some(models::RangeCondition::Number(value)) => Some(*value),
+        None => None,
+    }
+}
+
+fn field_condition_matches(condition: &FieldCondition, chunk: &ChunkMetadata) -> bool {
+    let values = field_values(&condition.field, chunk);
+    let matches_any = |conditions: &[MatchCondition]| {
+        values.iter().any(|value| {
+            conditions
+                .iter()
+                .any(|condition_value| value_matches(&condition.field, value, condition_value))
+        })
+    };
+
+    if let Some(conditions) = condition.match_any.clone().flatten() {
+        if !matches_any(&conditions) {
+            return false;
+        }
+    }
+    if let Some(conditions) = condition.match_all.clone().flatten() {
+        if !conditions
+            .iter()
+            .all(|condition| matches_any(std::slice::from_ref(condition)))
+        {
+            return false;
+        }
+    }
+    if let Some(range) = condition.range.clone().flatten() {
+        let in_range = |value: f64| {
+            range_value(&range.gt).is_none_or(|bound| value > bound)
+                && range_value(&range.gte).is_none_or(|bound| value >= bound)
+                && range_value(&range.lt).is_none_or(|bound| value < bound)
+                && range_value(&range.lte).is_none_or(|bound| value <= bound)
+        };
+        if !values.iter().filter_map(Value::as_f64).any(in_range) {
+            return false;
+        }
+    }
+    true
+}
+
+fn condition_matches(condition: &CustomConditionType, chunk: &ChunkMetadata) -> bool {
+    match condition {
+        CustomConditionType::FieldCondition(condition) => field_condition_matches(condition, chunk),
+        CustomConditionType::ChunkFilter(filter) => filter_matches(filter, chunk),
+        CustomConditionType::HasIdCondition(condition) => {
+            condition
+                .ids
+                .clone()
+                .flatten()
+                .is_some_and(|ids| ids.contains(&chunk.id))
+                || condition
+                    .tracking_ids
+                    .clone()
+                    .flatten()
+                    .is_some_and(|tracking_ids| {
+                        chunk
+                            .tracking_id
+                            .clone()
+                            .flatten()
+                            .is_some_and(|tracking_id| tracking_ids.contains(&tracking_id))
+                    })
+        }
+    }
+}
+
+/// Whether the chunk passes a filter: all of `must`, none of `must_not` and at least one
+/// of `should` when it's not empty.
+pub fn filter_matches(filter: &CustomChunkFilter, chunk: &ChunkMetadata) -> bool {
+    let must = filter.must.clone().flatten().unwrap_or_default();
+    let must_not = filter.must_not.clone().flatten().unwrap_or_default();
+    let should = filter.should.clone().flatten().unwrap_or_default();
+
+    must.iter()
+        .all(|condition| condition_matches(condition, chunk))
+        && !must_not
+            .iter()
+            .any(|condition| condition_matches(condition, chunk))
+        && (should.is_empty()
+            || should
+                .iter()
+                .any(|condition| condition_matches(condition, chunk)))
+}
+
+fn sort_value(field: &str, chunk: &ChunkMetadata) -> f64 {
+    field_values(field, chunk)
+        .iter()
+        .find_map(Value::as_f64)
+        .unwrap_or(f64::MIN)
+}
+
+fn score_chunk(chunk: &ChunkMetadata, score: f32) -> ScoreChunkMetadata {
+    ScoreChunkMetadata {
+        chunk: chunk.clone(),
+        highlights: None,
+        score,
+        snippets: vec![],
+        parent: None,
+    }
+}
+
+fn page_of<T>(items: Vec<T>, page: i64, page_size: i64) -> Vec<T> {
+    let skip = ((page.max(1) - 1) * page_size.max(0)) as usize;
+    items
+        .into_iter()
+        .skip(skip)
+        .take(page_size.max(0) as usize)
+        .collect()
+}
+
+fn total_pages(num_items: usize, page_size: i64) -> i64 {
+    (num_items as i64 + page_size.max(1) - 1) / page_size.max(1)
+}
+
+/// The chunk the ingest would create for `item`, given the id and title of its story.
+pub fn item_to_chunk(item: &HnItem, top_parent: Option<(i64, Option<String>)>) -> ChunkMetadata {
+    let mut tags = vec![item.item_type.clone(), item.by.clone()];
+    let title = item.title.clone().unwrap_or_default();
+    if title.starts_with("Show HN:") {
+        tags.push("show".to_string());
+    }
+    if title.starts_with("Ask HN:") {
+        tags.push("ask".to_string());
+    }
+    if let Some(url) = &item.url {
+        let host = url
+            .split_once("://")
+            .map_or(url.as_str(), |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        if host == "github.com" {
+            if let Some(owner) = url.split('/').nth(3) {
+                tags.push(format!("github.com/{}", owner));
+            }
+        }
+        tags.push(host.to_string());
+    }
+    let (top_parent_id, parent_title) = top_parent.unwrap_or((-1, None));
+    if item.item_type == "comment" && item.parent == Some(top_parent_id) {
+        tags.push(Predicate::TopLevel.tag().to_string());
+    }
+    for (field, value) in [
+        (TextField::Title, &item.title),
+        (TextField::Text, &item.text),
+        (TextField::Url, &item.url),
+    ] {
+        if value.as_deref().is_some_and(|value| !value.is_empty()) {
+            tags.push(field.has_tag().to_string());
+        }
+    }
+
+    let mut html = String::new();
+    for part in [&item.title, &item.text, &item.url].into_iter().flatten() {
+        html.push_str(part);
+        html.push_str(" \n\n");
+    }
+
+    let time_stamp = DateTime::from_timestamp(item.time, 0)
+        .map(|time| time.naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string());
+    let created_at = time_stamp.clone().unwrap_or_default();
+
+    let mut chunk = ChunkMetadata::new(
+        created_at.clone(),
+        Uuid::nil(),
+        Uuid::from_u128(item.id as u128),
+        created_at,
+        0.0,
+    );
+    chunk.chunk_html = Some(Some(html));
+    chunk.link = Some(item.url.clone());
+    chunk.metadata = Some(Some(json!({
+        "by": item.by,
+        "descendants": item.descendants.unwrap_or(0).max(item.kids.len() as i64),
+        "top_parent_id": top_parent_id,
+        "parent": item.parent,
+        "id": item.id,
+        "kids": item.kids,
+        "score": item.score,
+        "time": item.time,
+        "title": title,
+        "text": item.text.clone().unwrap_or_default(),
+        "parent_title": parent_title,
+        "type": item.item_type,
+        "url": item.url,
+    })));
+    chunk.num_value = Some(Some(item.score as f64));
+    chunk.tag_set = Some(Some(tags));
+    chunk.time_stamp = Some(time_stamp);
+    chunk.tracking_id = Some(Some(item.id.to_string()));
+    chunk
+}
+
+impl InMemoryBackend {
+    pub fn new(chunks: Vec<ChunkMetadata>) -> Self {
+        InMemoryBackend { chunks }
+    }
+
+    /// Turns HN items into chunks, resolving each comment's story among the other items.
+    pub fn from_items(items: Vec<HnItem>) -> Self {
+        let by_id = items
+            .iter()
+            .map(|item| (item.id, item))
+            .collect::<HashMap<i64, &HnItem>>();
+        let top_parent = |item: &HnItem| {
+            let mut current = item;
+            while let Some(parent) = current.parent.and_then(|parent| by_id.get(&parent)) {
+                current = parent;
+            }
+            (current.id != item.id).then(|| (current.id, current.title.clone()))
+        };
+
+        InMemoryBackend::new(
+            items
+                .iter()
+                .map(|item| item_to_chunk(item, top_parent(item)))
+                .collect(),
+        )
+    }
+
+    /// Loads a JSON array of HN items.
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let file = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let items = serde_json::from_str::<Vec<HnItem>>(&file).map_err(|err| err.to_string())?;
+        Ok(InMemoryBackend::from_items(items))
+    }
+
+    /// Chunks passing `filters` and matching `query`, best first.
+    fn matching(
+        &self,
+        query: &str,
+        filters: Option<&CustomChunkFilter>,
+    ) -> Vec<(f32, &ChunkMetadata)> {
+        let terms = QueryTerms::parse(query);
+        let mut matches = self
+            .chunks
+            .iter()
+            .filter(|chunk| filters.is_none_or(|filters| filter_matches(filters, chunk)))
+            .filter_map(|chunk| terms.score(chunk).map(|score| (score, chunk)))
+            .collect::<Vec<(f32, &ChunkMetadata)>>();
+        matches.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        matches
+    }
+}
+
+#[async_trait]
+impl SearchBackend for InMemoryBackend {
+    async fn search(
+        &self,
+        payload: &CustomSearchChunksReqPayload,
+    ) -> Result<SimplifiedSearchResponse, SearchError> {
+        let query = match payload.query.as_ref() {
+            models::QueryTypes::String(query) => query.clone(),
+            models::QueryTypes::Array(_) => {
+                return Err(SearchError::BadRequest(
+                    "multi-queries aren't supported in memory".to_string(),
+                ))
+            }
+        };
+        let filters = payload.filters.clone().flatten();
+        let mut matches = self.matching(&query, filters.as_deref());
+
+        let sort_by = payload
+            .sort_options
+            .clone()
+            .flatten()
+            .and_then(|sort_options| sort_options.sort_by.flatten())
+            .and_then(|sort_by| match *sort_by {
+                QdrantSortBy::SortByField(sort_by) => Some(sort_by),
+                QdrantSortBy::SortBySearchType(_) => None,
+            });
+        if let Some(sort_by) = sort_by {
+            let field = sort_by.field;
+            matches
+                .sort_by(|(_, a), (_, b)| sort_value(&field, b).total_cmp(&sort_value(&field, a)));
+            if sort_by.direction.flatten() == Some(SortOrder::Asc) {
+                matches.reverse();
+            }
+        }
+
+        let page_size = payload.page_size.flatten().unwrap_or(10);
+        let num_matches = matches.len();
+        Ok(SimplifiedSearchResponse {
+            chunks: page_of(matches, payload.page.flatten().unwrap_or(1), page_size)
+                .into_iter()
+                .map(|(score, chunk)| score_chunk(chunk, score))
+                .collect(),
+            total_pages: (payload.get_total_pages.flatten() == Some(true))
+                .then(|| total_pages(num_matches, page_size)),
+            ..Default::default()
+        })
+    }
+
+    async fn get_chunk(&self, tracking_id: &str) -> Result<Option<ChunkMetadata>, SearchError> {
+        Ok(self
+            .chunks
+            .iter()
+            .find(|chunk| chunk.tracking_id.clone().flatten().as_deref() == Some(tracking_id))
+            .cloned())
+    }
+
+    async fn get_chunks(&self, tracking_ids: &[String]) -> Result<Vec<ChunkMetadata>, SearchError> {
+        Ok(self
+            .chunks
+            .iter()
+            .filter(|chunk| {
+                chunk
+                    .tracking_id
+                    .clone()
+                    .flatten()
+                    .is_some_and(|tracking_id| tracking_ids.contains(&tracking_id))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn recommend(
+        &self,
+        payload: &RecommendChunksPayload,
+    ) -> Result<Vec<ScoreChunkMetadata>, SearchError> {
+        let is_in = |chunk: &ChunkMetadata, tracking_ids: &[String]| {
+            chunk
+                .tracking_id
+                .clone()
+                .flatten()
+                .is_some_and(|tracking_id| tracking_ids.contains(&tracking_id))
+        };
+        let words = |chunk: &ChunkMetadata| {
+            searchable_text(chunk)
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| word.len() > 3)
+                .map(str::to_string)
+                .collect::<HashSet<String>>()
+        };
+        let words_of = |tracking_ids: &[String]| {
+            self.chunks
+                .iter()
+                .filter(|chunk| is_in(chunk, tracking_ids))
+                .flat_map(words)
+                .collect::<HashSet<String>>()
+        };
+        let positive_words = words_of(&payload.positive_tracking_ids);
+        // Words of both kinds of examples don't tell them apart.
+        let negative_words = words_of(&payload.negative_tracking_ids)
+            .difference(&positive_words)
+            .cloned()
+            .collect::<HashSet<String>>();
+
+        let mut recommendations = self
+            .chunks
+            .iter()
+            .filter(|chunk| {
+                !is_in(chunk, &payload.positive_tracking_ids)
+                    && !is_in(chunk, &payload.negative_tracking_ids)
+            })
+            .filter(|chunk| {
+                payload
+                    .filters
+                    .as_ref()
+                    .is_none_or(|filters| filter_matches(filters, chunk))
+            })
+            .filter_map(|chunk| {
+                let words = words(chunk);
+                let score = words.intersection(&positive_words).count() as f32
+                    - words.intersection(&negative_words).count() as f32;
+                (score > 0.0).then_some((score, chunk))
+            })
+            .collect::<Vec<(f32, &ChunkMetadata)>>();
+        recommendations.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        Ok(recommendations
+            .into_iter()
+            .take(payload.limit.max(0) as usize)
+            .map(|(score, chunk)| score_chunk(chunk, score))
+            .collect())
+    }
+
+    async fn group_search(
+        &self,
+        payload: &GroupSearchPayload,
+    ) -> Result<GroupSearchResponse, SearchError> {
+        let mut groups: Vec<(i64, Vec<ScoreChunkMetadata>)> = vec![];
+        for (score, chunk) in self.matching(&payload.query, payload.filters.as_ref()) {
+            let Some(story_id) = field_values("metadata.top_parent_id", chunk)
+                .first()
+                .and_then(Value::as_i64)
+                .filter(|story_id| *story_id > 0)
+            else {
+                continue;
+            };
+            match groups.iter_mut().find(|(id, _)| *id == story_id) {
+                Some((_, chunks)) => chunks.push(score_chunk(chunk, score)),
+                None => groups.push((story_id, vec![score_chunk(chunk, score)])),
+            }
+        }
+
+        let num_groups = groups.len();
+        Ok(GroupSearchResponse {
+            results: page_of(groups, payload.page, payload.page_size)
+                .into_iter()
+                .map(|(story_id, chunks)| GroupResult {
+                    group: GroupInfo {
+                        name: story_id.to_string(),
+                        tracking_id: Some(story_id.to_string()),
+                    },
+                    chunks: chunks
+                        .into_iter()
+                        .take(payload.group_size.max(0) as usize)
+                        .collect(),
+                })
+                .collect(),
+            total_pages: payload
+                .get_total_pages
+                .then(|| total_pages(num_groups, payload.page_size)),
+        })
+    }
+
+    async fn scroll(
+        &self,
+        payload: &ScrollChunksPayload,
+    ) -> Result<Vec<ChunkMetadata>, SearchError> {
+        let mut chunks = self
+            .chunks
+            .iter()
+            .filter(|chunk| {
+                payload
+                    .filters
+                    .as_ref()
+                    .is_none_or(|filters| filter_matches(filters, chunk))
+            })
+            .filter(|chunk| {
+                payload.sort_by.is_some()
+                    || payload
+                        .offset_chunk_id
+                        .is_none_or(|offset| chunk.id >= offset)
+            })
+            .cloned()
+            .collect::<Vec<ChunkMetadata>>();
+        chunks.sort_by_key(|chunk| chunk.id);
+        if let Some(sort_by) = &payload.sort_by {
+            let field = &sort_by.field;
+            chunks.sort_by(|a, b| sort_value(field, b).total_cmp(&sort_value(field, a)));
+            if sort_by.direction.flatten() == Some(SortOrder::Asc) {
+                chunks.reverse();
+            }
+        }
+        chunks.truncate(payload.page_size.max(0) as usize);
+        Ok(chunks)
+    }
+
+    async fn count(&self, payload: &CountChunksPayload) -> Result<usize, SearchError> {
+        Ok(self
+            .matching(
+                payload.query.as_deref().unwrap_or_default(),
+                payload.filters.as_ref(),
+            )
+            .len())
+    }
+}
@@ -0,0 +1,228 @@
+//! Where searches are run: [`trieve::TrieveBackend`] calls the Trieve API, while
+//! [`memory::InMemoryBackend`] searches a fixture of HN items so the app and its tests run
+//! without network access.
+
+pub mod cache;
+pub mod circuit_breaker;
+pub mod memory;
+pub mod trieve;
+
+use crate::{
+    errors::SearchError,
+    handlers::search_handler::{
+        CustomChunkFilter, CustomSearchChunksReqPayload, ScoreChunkMetadata,
+        SimplifiedSearchResponse,
+    },
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use trieve_client::models::{self, ChunkMetadata};
+use uuid::Uuid;
+
+pub use cache::CachedBackend;
+pub use memory::InMemoryBackend;
+pub use trieve::TrieveBackend;
+
+/// Chunks similar to the positive ones and unlike the negative ones, which are all left out
+/// of the results.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RecommendChunksPayload {
+    pub positive_tracking_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub negative_tracking_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<CustomChunkFilter>,
+    pub limit: i64,
+}
+
+/// A search whose results are grouped by story, each group holding the story's comments.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GroupSearchPayload {
+    pub query: String,
+    pub search_type: models::SearchMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<CustomChunkFilter>,
+    pub page: i64,
+    pub page_size: i64,
+    /// Chunks returned per group.
+    pub group_size: i32,
+    pub get_total_pages: bool,
+}
+
+/// A page of the chunks passing `filters`, in the order of their ids. Used to list chunks,
+/// such as the comments of a story, rather than to search them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ScrollChunksPayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<CustomChunkFilter>,
+    /// Id of the first chunk of the page, which starts at the first chunk when unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset_chunk_id: Option<Uuid>,
+    pub page_size: i64,
+    /// Orders the chunks by a field instead of by id. Only the first page is fetched then,
+    /// as pages can't start at an offset chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<models::SortByField>,
+}
+
+/// Counts the chunks passing `filters` and matching `query`, or all of the ones passing
+/// `filters` when there is no query.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CountChunksPayload {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    pub search_type: models::CountSearchMethod,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filters: Option<CustomChunkFilter>,
+}
+
+/// The parts of a Trieve chunk group the frontend uses. The ingest names each group after
+/// the id of its story and uses that id as the tracking id.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GroupInfo {
+    pub name: String,
+    pub tracking_id: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GroupResult {
+    pub group: GroupInfo,
+    pub chunks: Vec<ScoreChunkMetadata>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GroupSearchResponse {
+    pub results: Vec<GroupResult>,
+    pub total_pages: Option<i64>,
+}
+
+/// Chunks fetched per scroll call.
+const SCROLL_PAGE_SIZE: i64 = 1000;
+
+/// Chunks listed by [`scroll_all`].
+#[derive(Clone, Debug, Default)]
+pub struct ScrolledChunks {
+    pub chunks: Vec<ChunkMetadata>,
+    /// More chunks passed the filters than were fetched.
+    pub truncated: bool,
+}
+
+/// All the chunks passing `filters`, scrolled page by page until there are no more or
+/// `max_chunks` of them were fetched.
+pub async fn scroll_all(
+    backend: &dyn SearchBackend,
+    filters: CustomChunkFilter,
+    max_chunks: usize,
+) -> Result<ScrolledChunks, SearchError> {
+    let mut chunks = vec![];
+    let mut offset_chunk_id = None;
+    // One chunk past the limit tells whether there are more.
+    while chunks.len() <= max_chunks {
+        let page = backend
+            .scroll(&ScrollChunksPayload {
+                filters: Some(filters.clone()),
+                offset_chunk_id,
+                page_size: SCROLL_PAGE_SIZE,
+                sort_by: None,
+            })
+            .await?;
+        let num_chunks = page.len();
+        // Pages start at their offset chunk, which ended the previous page.
+        let new_chunks = page
+            .into_iter()
+            .filter(|chunk| Some(chunk.id) != offset_chunk_id)
+            .collect::<Vec<ChunkMetadata>>();
+        let Some(last) = new_chunks.last() else {
+            break;
+        };
+        offset_chunk_id = Some(last.id);
+        chunks.extend(new_chunks);
+        if (num_chunks as i64) < SCROLL_PAGE_SIZE {
+            break;
+        }
+    }
+    let truncated = chunks.len() > max_chunks;
+    chunks.truncate(max_chunks);
+    Ok(ScrolledChunks { chunks, truncated })
+}
+
+/// Number of chunks passing `filters`.
+pub async fn count_chunks(
+    backend: &dyn SearchBackend,
+    filters: CustomChunkFilter,
+) -> Result<usize, SearchError> {
+    backend
+        .count(&CountChunksPayload {
+            query: None,
+            search_type: models::CountSearchMethod::Fulltext,
+            filters: Some(filters),
+        })
+        .await
+}
+
+/// The first `limit` chunks passing `filters` when sorted by `field`, such as the stories
+/// of a user with the most points. They're scrolled rather than searched, so that no query
+/// is needed.
+pub async fn sorted_chunks(
+    backend: &dyn SearchBackend,
+    filters: CustomChunkFilter,
+    field: &str,
+    direction: models::SortOrder,
+    limit: usize,
+) -> Result<Vec<ScoreChunkMetadata>, SearchError> {
+    let chunks = backend
+        .scroll(&ScrollChunksPayload {
+            filters: Some(filters),
+            offset_chunk_id: None,
+            page_size: limit as i64,
+            sort_by: Some(models::SortByField {
+                field: field.to_string(),
+                direction: Some(Some(direction)),
+                prefetch_amount: None,
+            }),
+        })
+        .await?;
+    Ok(chunks
+        .into_iter()
+        .map(|chunk| ScoreChunkMetadata {
+            chunk,
+            highlights: None,
+            score: 0.0,
+            snippets: vec![],
+            parent: None,
+        })
+        .collect())
+}
+
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    /// Runs a search. Diagnostics are left to the caller.
+    async fn search(
+        &self,
+        payload: &CustomSearchChunksReqPayload,
+    ) -> Result<SimplifiedSearchResponse, SearchError>;
+
+    /// Fetches a chunk by its tracking id, which is the id of the HN item.
+    async fn get_chunk(&self, tracking_id: &str) -> Result<Option<ChunkMetadata>, SearchError>;
+
+    /// Fetches the chunks of several HN items at once, leaving out the missing ones.
+    async fn get_chunks(&self, tracking_ids: &[String]) -> Result<Vec<ChunkMetadata>, SearchError>;
+
+    async fn recommend(
+        &self,
+        payload: &RecommendChunksPayload,
+    ) -> Result<Vec<ScoreChunkMetadata>, SearchError>;
+
+    async fn group_search(
+        &self,
+        payload: &GroupSearchPayload,
+    ) -> Result<GroupSearchResponse, SearchError>;
+
+    async fn scroll(
+        &self,
+        payload: &ScrollChunksPayload,
+    ) -> Result<Vec<ChunkMetadata>, SearchError>;
+
+    /// Number of chunks passing the filters and matching the query, without fetching them.
+    async fn count(&self, payload: &CountChunksPayload) -> Result<usize, SearchError>;
+}
@@ -0,0 +1,227 @@
+use super::{
+    circuit_breaker::CircuitBreaker, CountChunksPayload, GroupSearchPayload, GroupSearchResponse,
+    RecommendChunksPayload, ScrollChunksPayload, SearchBackend,
+};
+use crate::{
+    config::TrieveConfig,
+    errors::SearchError,
+    handlers::search_handler::{
+        CustomSearchChunksReqPayload, ScoreChunkMetadata, SimplifiedSearchResponse,
+    },
+};
+use async_trait::async_trait;
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    Method, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    time::Duration,
+};
+use trieve_client::models::ChunkMetadata;
+
+/// Runs searches against the Trieve API of the configured dataset.
+pub struct TrieveBackend {
+    client: reqwest::Client,
+    config: TrieveConfig,
+    breaker: CircuitBreaker,
+}
+
+#[derive(Deserialize)]
+struct RecommendChunksResponse {
+    chunks: Vec<ScoreChunkMetadata>,
+}
+
+#[derive(Deserialize)]
+struct ScrollChunksResponse {
+    chunks: Vec<ChunkMetadata>,
+}
+
+#[derive(Deserialize)]
+struct CountChunksResponse {
+    count: usize,
+}
+
+impl TrieveBackend {
+    pub fn new(client: reqwest::Client, config: TrieveConfig) -> Self {
+        let breaker = CircuitBreaker::new(
+            config.circuit_breaker_threshold,
+            Duration::from_millis(config.circuit_breaker_cooldown_ms),
+        );
+        TrieveBackend {
+            client,
+            config,
+            breaker,
+        }
+    }
+
+    /// Builds a client with the configured timeouts, so a slow Trieve can't hang workers.
+    pub fn from_config(config: TrieveConfig) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout_ms))
+            .read_timeout(Duration::from_millis(config.read_timeout_ms))
+            .timeout(Duration::from_millis(config.request_timeout_ms))
+            .build()?;
+        Ok(TrieveBackend::new(client, config))
+    }
+
+    fn headers(&self) -> HeaderMap {
+        let mut header_map = HeaderMap::new();
+        header_map.insert("Content-Type", HeaderValue::from_static("application/json"));
+        header_map.insert(
+            "Authorization",
+            HeaderValue::from_str(&self.config.api_key).unwrap(),
+        );
+        header_map.insert(
+            "TR-Dataset",
+            HeaderValue::from_str(&self.config.dataset_id).unwrap(),
+        );
+        header_map.insert("X-API-Version", HeaderValue::from_static("V2"));
+        header_map
+    }
+
+    /// Sends a request to `path`, returning `None` when Trieve answers with a 404. All calls
+    /// only read, so those that time out or find Trieve unavailable are retried.
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<Option<T>, SearchError> {
+        let mut attempt = 0;
+        loop {
+            if !self.breaker.allows_call() {
+                return Err(SearchError::CircuitOpen);
+            }
+            let result = self.send_once(method.clone(), path, body).await;
+            match &result {
+                Err(err) if err.is_transient() => self.breaker.record_failure(),
+                _ => self.breaker.record_success(),
+            }
+            match result {
+                Err(err) if err.is_transient() && attempt < self.config.max_retries => {
+                    tracing::warn!("Retrying {} after: {}", path, err);
+                    actix_web::rt::time::sleep(self.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Delay before retry number `attempt`, doubled on each retry with up to half of it
+    /// taken off at random so retries from many requests don't line up.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .config
+            .retry_backoff_ms
+            .saturating_mul(1 << attempt.min(16));
+        let random = RandomState::new().build_hasher().finish();
+        Duration::from_millis(delay - random % (delay / 2 + 1))
+    }
+
+    async fn send_once<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&impl Serialize>,
+    ) -> Result<Option<T>, SearchError> {
+        let mut request = self
+            .client
+            .request(method, format!("{}{}", self.config.api_url, path))
+            .headers(self.headers());
+        if let Some(body) = body {
+            request = request.body(serde_json::to_string(body).unwrap());
+        }
+        let resp = request.send().await?;
+
+        let status = resp.status();
+        let resp_text = resp.text().await?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => SearchError::AuthFailure,
+                StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => SearchError::Timeout,
+                status if status.is_client_error() => SearchError::BadRequest(resp_text),
+                status => SearchError::UpstreamUnavailable(format!("{}: {}", status, resp_text)),
+            });
+        }
+
+        serde_json::from_str::<T>(&resp_text)
+            .map(Some)
+            .map_err(|err| SearchError::Decode(err.to_string()))
+    }
+
+    async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl Serialize,
+    ) -> Result<T, SearchError> {
+        self.send(Method::POST, path, Some(body))
+            .await?
+            .ok_or_else(|| SearchError::BadRequest(format!("{} was not found", path)))
+    }
+}
+
+#[async_trait]
+impl SearchBackend for TrieveBackend {
+    async fn search(
+        &self,
+        payload: &CustomSearchChunksReqPayload,
+    ) -> Result<SimplifiedSearchResponse, SearchError> {
+        self.post("/api/chunk/search", payload).await
+    }
+
+    async fn get_chunk(&self, tracking_id: &str) -> Result<Option<ChunkMetadata>, SearchError> {
+        self.send(
+            Method::GET,
+            &format!("/api/chunk/tracking_id/{}", tracking_id),
+            None::<&()>,
+        )
+        .await
+    }
+
+    async fn get_chunks(&self, tracking_ids: &[String]) -> Result<Vec<ChunkMetadata>, SearchError> {
+        self.post(
+            "/api/chunks/tracking",
+            &serde_json::json!({ "tracking_ids": tracking_ids }),
+        )
+        .await
+    }
+
+    async fn recommend(
+        &self,
+        payload: &RecommendChunksPayload,
+    ) -> Result<Vec<ScoreChunkMetadata>, SearchError> {
+        self.post::<RecommendChunksResponse>("/api/chunk/recommend", payload)
+            .await
+            .map(|resp| resp.chunks)
+    }
+
+    async fn group_search(
+        &self,
+        payload: &GroupSearchPayload,
+    ) -> Result<GroupSearchResponse, SearchError> {
+        self.post("/api/chunk_group/group_oriented_search", payload)
+            .await
+    }
+
+    async fn scroll(
+        &self,
+        payload: &ScrollChunksPayload,
+    ) -> Result<Vec<ChunkMetadata>, SearchError> {
+        self.post::<ScrollChunksResponse>("/api/chunks/scroll", payload)
+            .await
+            .map(|resp| resp.chunks)
+    }
+
+    async fn count(&self, payload: &CountChunksPayload) -> Result<usize, SearchError> {
+        self.post::<CountChunksResponse>("/api/chunk/count", payload)
+            .await
+            .map(|resp| resp.count)
+    }
+}
@@ -0,0 +1,506 @@
+use crate::handlers::search_request::SearchType;
+use serde::Deserialize;
+use std::{fmt, path::PathBuf, str::FromStr};
+use trieve_client::models;
+
+/// TOML file read when `CONFIG_FILE` isn't set. It's fine for it not to exist.
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Everything the server needs, loaded and validated once at startup from the optional
+/// TOML file, overridden by env vars (including the ones in `.env`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind_address: String,
+    pub static_dir: PathBuf,
+    pub search_backend: BackendKind,
+    /// JSON array of HN items searched by the `memory` backend.
+    pub fixtures: PathBuf,
+    pub trieve: TrieveConfig,
+    pub search: SearchConfig,
+    pub cache: CacheConfig,
+}
+
+/// Where searches run: the Trieve API, or HN items loaded in memory for offline dev.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Trieve,
+    Memory,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trieve" => Ok(BackendKind::Trieve),
+            "memory" => Ok(BackendKind::Memory),
+            _ => Err(format!("expected trieve or memory, not `{}`", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrieveConfig {
+    pub api_url: String,
+    pub api_key: String,
+    pub dataset_id: String,
+    pub connect_timeout_ms: u64,
+    /// Longest wait for each read of the response.
+    pub read_timeout_ms: u64,
+    /// Longest a whole request can take, including reading the response.
+    pub request_timeout_ms: u64,
+    /// Times a call is retried after it timed out or Trieve was unavailable.
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled on each one and jittered.
+    pub retry_backoff_ms: u64,
+    /// Consecutive failed calls after which calls fail fast without reaching Trieve.
+    pub circuit_breaker_threshold: u32,
+    /// How long calls fail fast before Trieve is tried again.
+    pub circuit_breaker_cooldown_ms: u64,
+}
+
+impl Default for TrieveConfig {
+    fn default() -> Self {
+        TrieveConfig {
+            api_url: String::new(),
+            api_key: String::new(),
+            dataset_id: String::new(),
+            connect_timeout_ms: 2_000,
+            read_timeout_ms: 5_000,
+            request_timeout_ms: 10_000,
+            max_retries: 2,
+            retry_backoff_ms: 100,
+            circuit_breaker_threshold: 5,
+            circuit_breaker_cooldown_ms: 30_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchConfig {
+    /// Used when `search_type` isn't in the query params.
+    pub default_search_type: SearchType,
+    pub default_page_size: i64,
+    /// Larger page sizes are clamped down to this.
+    pub max_page_size: i64,
+    /// Deepest page which can be requested, Trieve gets slow past it.
+    pub max_page: i64,
+    /// Most items a similar page can be asked to be unlike, each one making the
+    /// recommendation larger.
+    pub max_negative: usize,
+    pub score_thresholds: ScoreThresholds,
+    /// Highlight options sent to Trieve.
+    pub highlight: HighlightConfig,
+    /// Words kept on each side of the query's words in the snippets of comments and text
+    /// posts, when Trieve sent no highlights for them.
+    pub snippet_window: usize,
+}
+
+/// Cache of search responses, kept in the process unless `redis_url` is set.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Most responses kept in memory, 0 turns the cache off.
+    pub capacity: usize,
+    /// How long responses are reused, also sent as the `max-age` of search pages.
+    pub ttl_secs: u64,
+    /// Shares the cache between processes, needs the `redis` feature.
+    pub redis_url: String,
+}
+
+/// Minimum score of a result for each search type, as their scores aren't comparable.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreThresholds {
+    pub fulltext: f32,
+    pub semantic: f32,
+    pub hybrid: f32,
+    pub keyword: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightConfig {
+    pub strategy: HighlightStrategy,
+    pub delimiters: Vec<String>,
+    pub threshold: f64,
+    pub max_num: i32,
+    /// Words Trieve keeps on each side of the matched words in a highlight.
+    pub window: i32,
+    pub max_length: i32,
+}
+
+/// How Trieve finds the words to highlight: `exactmatch` only highlights the query's words,
+/// `v1` also highlights similar words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStrategy {
+    #[default]
+    Exactmatch,
+    V1,
+}
+
+impl FromStr for HighlightStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "exactmatch" => Ok(HighlightStrategy::Exactmatch),
+            "v1" => Ok(HighlightStrategy::V1),
+            _ => Err(format!("expected exactmatch or v1, not `{}`", s)),
+        }
+    }
+}
+
+impl HighlightStrategy {
+    pub fn trieve(self) -> models::HighlightStrategy {
+        match self {
+            HighlightStrategy::Exactmatch => models::HighlightStrategy::Exactmatch,
+            HighlightStrategy::V1 => models::HighlightStrategy::V1,
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            bind_address: "0.0.0.0:9000".to_string(),
+            static_dir: PathBuf::from("./static"),
+            search_backend: BackendKind::default(),
+            fixtures: PathBuf::from("./fixtures/hn_items.json"),
+            trieve: TrieveConfig::default(),
+            search: SearchConfig::default(),
+            cache: CacheConfig::default(),
+        }
+    }
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            default_search_type: SearchType::Fulltext,
+            default_page_size: 30,
+            max_page_size: 100,
+            max_page: 1000,
+            max_negative: 50,
+            score_thresholds: ScoreThresholds::default(),
+            highlight: HighlightConfig::default(),
+            snippet_window: 12,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            capacity: 1000,
+            ttl_secs: 60,
+            redis_url: String::new(),
+        }
+    }
+}
+
+impl Default for ScoreThresholds {
+    fn default() -> Self {
+        ScoreThresholds {
+            fulltext: 5.0,
+            semantic: 0.5,
+            hybrid: 0.01,
+            keyword: 1.5,
+        }
+    }
+}
+
+impl ScoreThresholds {
+    pub fn get(&self, search_type: SearchType) -> f32 {
+        match search_type {
+            SearchType::Fulltext => self.fulltext,
+            SearchType::Semantic => self.semantic,
+            SearchType::Hybrid => self.hybrid,
+            SearchType::Keyword => self.keyword,
+        }
+    }
+}
+
+impl Default for HighlightConfig {
+    fn default() -> Self {
+        HighlightConfig {
+            strategy: HighlightStrategy::default(),
+            delimiters: [" ", "-", "_", ".", ","]
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            threshold: 0.85,
+            max_num: 50,
+            window: 0,
+            max_length: 50,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The config file exists but can't be read or isn't valid TOML for `Config`.
+    File {
+        path: PathBuf,
+        reason: String,
+    },
+    Missing(&'static str),
+    Invalid {
+        name: &'static str,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File { path, reason } => {
+                write!(f, "could not load {}: {}", path.display(), reason)
+            }
+            ConfigError::Missing(name) => write!(
+                f,
+                "{} must be set, either as an env var or in the config file",
+                name
+            ),
+            ConfigError::Invalid { name, reason } => write!(f, "{} is invalid: {}", name, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn override_with<T: FromStr>(
+    target: &mut T,
+    name: &'static str,
+    env: &impl Fn(&str) -> Option<String>,
+) -> Result<(), ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    if let Some(value) = env(name).filter(|value| !value.trim().is_empty()) {
+        *target = value
+            .trim()
+            .parse()
+            .map_err(|err: T::Err| ConfigError::Invalid {
+                name,
+                reason: err.to_string(),
+            })?;
+    }
+    Ok(())
+}
+
+impl TrieveConfig {
+    fn validate(&self) -> Result<(), ConfigError> {
+        for (name, value) in [
+            ("TRIEVE_API_URL", &self.api_url),
+            ("TRIEVE_API_KEY", &self.api_key),
+            ("TRIEVE_DATASET_ID", &self.dataset_id),
+        ] {
+            if value.trim().is_empty() {
+                return Err(ConfigError::Missing(name));
+            }
+        }
+        if !self.api_url.starts_with("http://") && !self.api_url.starts_with("https://") {
+            return Err(ConfigError::Invalid {
+                name: "TRIEVE_API_URL",
+                reason: format!("`{}` is not an http(s) URL", self.api_url),
+            });
+        }
+        for (name, value) in [
+            ("TRIEVE_API_KEY", &self.api_key),
+            ("TRIEVE_DATASET_ID", &self.dataset_id),
+        ] {
+            if reqwest::header::HeaderValue::from_str(value).is_err() {
+                return Err(ConfigError::Invalid {
+                    name,
+                    reason: "it can't be sent as an HTTP header".to_string(),
+                });
+            }
+        }
+        for (name, value) in [
+            ("TRIEVE_CONNECT_TIMEOUT_MS", self.connect_timeout_ms),
+            ("TRIEVE_READ_TIMEOUT_MS", self.read_timeout_ms),
+            ("TRIEVE_REQUEST_TIMEOUT_MS", self.request_timeout_ms),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::Invalid {
+                    name,
+                    reason: "it must be at least 1".to_string(),
+                });
+            }
+        }
+        if self.circuit_breaker_threshold == 0 {
+            return Err(ConfigError::Invalid {
+                name: "TRIEVE_CIRCUIT_BREAKER_THRESHOLD",
+                reason: "it must be at least 1".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Config {
+    /// Loads `.env`, the TOML file at `CONFIG_FILE` (or `config.toml` when present) and
+    /// then the env vars.
+    pub fn load() -> Result<Config, ConfigError> {
+        dotenvy::dotenv().ok();
+
+        let (path, required) = match std::env::var("CONFIG_FILE") {
+            Ok(path) => (PathBuf::from(path), true),
+            Err(_) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+        let file = match std::fs::read_to_string(&path) {
+            Ok(file) => Some(file),
+            Err(err) if required || err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(ConfigError::File {
+                    path,
+                    reason: err.to_string(),
+                })
+            }
+            Err(_) => None,
+        };
+
+        Config::from_sources(file.as_deref(), |name| std::env::var(name).ok()).map_err(|err| {
+            match err {
+                ConfigError::File { reason, .. } => ConfigError::File { path, reason },
+                err => err,
+            }
+        })
+    }
+
+    /// Builds the config from the contents of a TOML file and env vars looked up with
+    /// `env`, which take precedence.
+    pub fn from_sources(
+        file: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut config = match file {
+            Some(file) => toml::from_str::<Config>(file).map_err(|err| ConfigError::File {
+                path: PathBuf::from(DEFAULT_CONFIG_FILE),
+                reason: err.message().to_string(),
+            })?,
+            None => Config::default(),
+        };
+
+        override_with(&mut config.bind_address, "BIND_ADDRESS", &env)?;
+        override_with(&mut config.static_dir, "STATIC_DIR", &env)?;
+        override_with(&mut config.search_backend, "SEARCH_BACKEND", &env)?;
+        override_with(&mut config.fixtures, "FIXTURES", &env)?;
+        override_with(&mut config.trieve.api_url, "TRIEVE_API_URL", &env)?;
+        override_with(&mut config.trieve.api_key, "TRIEVE_API_KEY", &env)?;
+        override_with(&mut config.trieve.dataset_id, "TRIEVE_DATASET_ID", &env)?;
+        override_with(
+            &mut config.trieve.connect_timeout_ms,
+            "TRIEVE_CONNECT_TIMEOUT_MS",
+            &env,
+        )?;
+        override_with(
+            &mut config.trieve.read_timeout_ms,
+            "TRIEVE_READ_TIMEOUT_MS",
+            &env,
+        )?;
+        override_with(
+            &mut config.trieve.request_timeout_ms,
+            "TRIEVE_REQUEST_TIMEOUT_MS",
+            &env,
+        )?;
+        override_with(&mut config.trieve.max_retries, "TRIEVE_MAX_RETRIES", &env)?;
+        override_with(
+            &mut config.trieve.retry_backoff_ms,
+            "TRIEVE_RETRY_BACKOFF_MS",
+            &env,
+        )?;
+        override_with(
+            &mut config.trieve.circuit_breaker_threshold,
+            "TRIEVE_CIRCUIT_BREAKER_THRESHOLD",
+            &env,
+        )?;
+        override_with(
+            &mut config.trieve.circuit_breaker_cooldown_ms,
+            "TRIEVE_CIRCUIT_BREAKER_COOLDOWN_MS",
+            &env,
+        )?;
+        override_with(
+            &mut config.search.default_search_type,
+            "DEFAULT_SEARCH_TYPE",
+            &env,
+        )?;
+        override_with(
+            &mut config.search.default_page_size,
+            "DEFAULT_PAGE_SIZE",
+            &env,
+        )?;
+        override_with(&mut config.search.max_page_size, "MAX_PAGE_SIZE", &env)?;
+        override_with(&mut config.search.max_page, "MAX_PAGE", &env)?;
+        override_with(&mut config.search.max_negative, "MAX_NEGATIVE", &env)?;
+        override_with(
+            &mut config.search.highlight.strategy,
+            "HIGHLIGHT_STRATEGY",
+            &env,
+        )?;
+        override_with(
+            &mut config.search.highlight.window,
+            "HIGHLIGHT_WINDOW",
+            &env,
+        )?;
+        override_with(&mut config.search.snippet_window, "SNIPPET_WINDOW", &env)?;
+        override_with(&mut config.cache.capacity, "CACHE_CAPACITY", &env)?;
+        override_with(&mut config.cache.ttl_secs, "CACHE_TTL_SECS", &env)?;
+        override_with(&mut config.cache.redis_url, "REDIS_URL", &env)?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.search_backend == BackendKind::Trieve {
+            self.trieve.validate()?;
+        }
+        if self.bind_address.parse::<std::net::SocketAddr>().is_err() {
+            return Err(ConfigError::Invalid {
+                name: "BIND_ADDRESS",
+                reason: format!("`{}` is not an ip:port address", self.bind_address),
+            });
+        }
+        if self.search.max_page < 1 {
+            return Err(ConfigError::Invalid {
+                name: "MAX_PAGE",
+                reason: "it must be at least 1".to_string(),
+            });
+        }
+        if self.search.max_page_size < 1 {
+            return Err(ConfigError::Invalid {
+                name: "MAX_PAGE_SIZE",
+                reason: "it must be at least 1".to_string(),
+            });
+        }
+        if !(1..=self.search.max_page_size).contains(&self.search.default_page_size) {
+            return Err(ConfigError::Invalid {
+                name: "DEFAULT_PAGE_SIZE",
+                reason: format!(
+                    "it must be between 1 and MAX_PAGE_SIZE ({})",
+                    self.search.max_page_size
+                ),
+            });
+        }
+        if self.search.highlight.window < 0 {
+            return Err(ConfigError::Invalid {
+                name: "HIGHLIGHT_WINDOW",
+                reason: "it can't be negative".to_string(),
+            });
+        }
+        if !self.cache.redis_url.is_empty() && !cfg!(feature = "redis") {
+            return Err(ConfigError::Invalid {
+                name: "REDIS_URL",
+                reason: "the server was built without the redis feature".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
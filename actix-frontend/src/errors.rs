@@ -0,0 +1,130 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+
+/// Why a search failed, so an outage isn't shown as "no results".
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchError {
+    /// The query params are out of bounds or not one of the accepted values.
+    InvalidParams(String),
+    /// Trieve could not be reached or answered with a server error.
+    UpstreamUnavailable(String),
+    /// Trieve failed too often lately, so it wasn't called.
+    CircuitOpen,
+    /// Trieve rejected the API key or dataset.
+    AuthFailure,
+    /// Trieve rejected the search request itself.
+    BadRequest(String),
+    /// Trieve answered with a body which isn't a search response.
+    Decode(String),
+    Timeout,
+}
+
+#[derive(Serialize)]
+struct ErrorResponseBody {
+    error: &'static str,
+    message: String,
+}
+
+impl SearchError {
+    /// Stable identifier of the error, used by the JSON API.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SearchError::InvalidParams(_) => "invalid_params",
+            SearchError::UpstreamUnavailable(_) => "upstream_unavailable",
+            SearchError::CircuitOpen => "circuit_open",
+            SearchError::AuthFailure => "auth_failure",
+            SearchError::BadRequest(_) => "bad_request",
+            SearchError::Decode(_) => "decode_error",
+            SearchError::Timeout => "timeout",
+        }
+    }
+
+    /// Whether the call may succeed if made again, as Trieve is down or slow rather than
+    /// rejecting it.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            SearchError::UpstreamUnavailable(_) | SearchError::Timeout
+        )
+    }
+
+    /// Message which is safe to show to users, without upstream details.
+    pub fn user_message(&self) -> String {
+        match self {
+            SearchError::InvalidParams(reason) => format!("Invalid search: {}.", reason),
+            SearchError::UpstreamUnavailable(_) | SearchError::Decode(_) => {
+                "Search is unavailable right now, please try again in a bit.".to_string()
+            }
+            SearchError::CircuitOpen => {
+                "Search is temporarily unavailable, please try again in a minute.".to_string()
+            }
+            SearchError::AuthFailure => {
+                "Search is misconfigured on our end, please let us know at humans@trieve.ai."
+                    .to_string()
+            }
+            SearchError::BadRequest(_) => {
+                "This search could not be run, try rephrasing it.".to_string()
+            }
+            SearchError::Timeout => {
+                "Search took too long to respond, please try again.".to_string()
+            }
+        }
+    }
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SearchError::InvalidParams(reason) => write!(f, "Invalid search params: {}", reason),
+            SearchError::UpstreamUnavailable(reason) => {
+                write!(f, "Trieve is unavailable: {}", reason)
+            }
+            SearchError::CircuitOpen => {
+                write!(f, "Trieve calls are failing fast after repeated failures")
+            }
+            SearchError::AuthFailure => write!(f, "Trieve rejected the API key or dataset"),
+            SearchError::BadRequest(reason) => {
+                write!(f, "Trieve rejected the search request: {}", reason)
+            }
+            SearchError::Decode(reason) => {
+                write!(f, "Could not decode the Trieve response: {}", reason)
+            }
+            SearchError::Timeout => write!(f, "Trieve timed out"),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+impl ResponseError for SearchError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SearchError::UpstreamUnavailable(_) | SearchError::CircuitOpen => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            SearchError::AuthFailure | SearchError::Decode(_) => StatusCode::BAD_GATEWAY,
+            SearchError::InvalidParams(_) | SearchError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            SearchError::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorResponseBody {
+            error: self.kind(),
+            message: self.user_message(),
+        })
+    }
+}
+
+impl From<reqwest::Error> for SearchError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            SearchError::Timeout
+        } else if err.is_decode() {
+            SearchError::Decode(err.to_string())
+        } else {
+            SearchError::UpstreamUnavailable(err.to_string())
+        }
+    }
+}
@@ -1,4 +1,25 @@
+use ammonia::{Builder, Url, UrlRelative};
 use chrono::{DateTime, Utc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
+
+/// Keeps the tags HN allows in texts, so markup coming from the HN API can't run scripts.
+/// Relative links point to HN, as they do on HN.
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::empty();
+    builder
+        .add_tags(["p", "a", "i", "pre", "code"])
+        .tag_attributes(HashMap::from([("a", HashSet::from(["href"]))]))
+        .generic_attributes(HashSet::new())
+        .url_schemes(HashSet::from(["http", "https"]))
+        .url_relative(UrlRelative::RewriteWithBase(
+            Url::parse("https://news.ycombinator.com/").unwrap(),
+        ))
+        .link_rel(Some("nofollow noopener noreferrer"));
+    builder
+});
 
 pub fn time_ago(timestamp: i64) -> String {
     let now: DateTime<Utc> = Utc::now();
@@ -68,6 +89,11 @@ pub fn format_link(url: &str) -> String {
     hostname.to_string().replace("www.", "")
 }
 
+/// HTML of an HN text or chunk with only HN's own tags left, safe to render as is.
+pub fn sanitize_html(html: &str) -> minijinja::Value {
+    minijinja::Value::from_safe_string(SANITIZER.clean(html).to_string())
+}
+
 pub fn round_score(num: f64) -> f64 {
     (num * 10000.0).round() / 10000.0
 }
@@ -0,0 +1,86 @@
+use super::{
+    page_handler::SearchQueryParams,
+    search_handler::{get_search_results, SimplifiedSearchResponse},
+    search_request::SearchRequest,
+};
+use crate::{
+    backend::{cache::CacheStats, SearchBackend},
+    config::Config,
+    errors::SearchError,
+};
+use actix_web::{get, web, HttpResponse};
+use serde::Deserialize;
+
+/// Options of the JSON API which the homepage doesn't take.
+#[derive(Debug, Deserialize, Default)]
+pub struct ApiSearchOptions {
+    /// Count the pages of the search, which makes it 50-200ms slower.
+    pub total_pages: Option<bool>,
+}
+
+/// Search Hacker News as JSON
+///
+/// Takes the same query params as the homepage and returns the same ranking as JSON: the
+/// matching chunks with their scores and highlights, any diagnostics about the query and the
+/// text query and filters it was parsed into. `total_pages` is only counted when asked for
+/// with `total_pages=true`.
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    tag = "search",
+    responses(
+        (status = 200, description = "Search results, empty when `q` is missing or empty", body = Object),
+        (status = 400, description = "Invalid query params, or Trieve rejected the search", body = Object),
+        (status = 502, description = "Trieve rejected the API key or sent an unexpected response", body = Object),
+        (status = 503, description = "Trieve is unavailable", body = Object),
+        (status = 504, description = "Trieve timed out", body = Object),
+    ),
+    params(
+        ("q" = Option<String>, Query, description = "Search query with inline filters"),
+        ("page" = Option<i64>, Query, description = "Page number, from 1 to `MAX_PAGE` (1000 by default)"),
+        ("page_size" = Option<i64>, Query, description = "Number of items per page, at most `MAX_PAGE_SIZE` (100 by default)"),
+        ("order_by" = Option<String>, Query, description = "`relevance`, `points`, `date` or `comments`"),
+        ("search_type" = Option<String>, Query, description = "`fulltext`, `semantic`, `hybrid`, or `keyword` for the search type"),
+        ("post_type" = Option<String>, Query, description = "`all`, `story`, `comment`, `show`, `job` or `poll`"),
+        ("total_pages" = Option<bool>, Query, description = "Also return the number of pages, at the cost of a slower search")
+    )
+)]
+#[get("/api/search")]
+pub async fn search(
+    config: web::Data<Config>,
+    backend: web::Data<dyn SearchBackend>,
+    query_params: web::Query<SearchQueryParams>,
+    options: web::Query<ApiSearchOptions>,
+) -> Result<HttpResponse, SearchError> {
+    let request = SearchRequest::new(&query_params, &config.search)?;
+    let search_resp = if request.q.is_empty() {
+        SimplifiedSearchResponse::default()
+    } else {
+        get_search_results(
+            backend.get_ref(),
+            &config,
+            &request,
+            options.total_pages.unwrap_or(false),
+        )
+        .await?
+    };
+
+    Ok(HttpResponse::Ok().json(search_resp))
+}
+
+/// Search cache hits and misses
+///
+/// Counts of the searches answered from the response cache and of those sent to Trieve since
+/// the server started.
+#[utoipa::path(
+    get,
+    path = "/api/cache_stats",
+    tag = "metrics",
+    responses(
+        (status = 200, description = "`hits` and `misses` of the search cache", body = Object),
+    )
+)]
+#[get("/api/cache_stats")]
+pub async fn cache_stats(stats: web::Data<CacheStats>) -> HttpResponse {
+    HttpResponse::Ok().json(stats.snapshot())
+}
@@ -0,0 +1,102 @@
+use super::page_handler::{cacheable_html, render_failed};
+use crate::{
+    backend::SearchBackend,
+    config::Config,
+    errors::SearchError,
+    thread::{build_thread, fetch_comments, ThreadNode, MAX_THREAD_COMMENTS},
+    Templates,
+};
+use actix_web::{
+    get,
+    http::{
+        header::{CacheControl, CacheDirective},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use minijinja::context;
+
+/// Story or comment along with the thread of replies below it, and whether comments of
+/// the story were left out for being too many.
+async fn get_thread(
+    backend: &dyn SearchBackend,
+    id: i64,
+) -> Result<Option<(ThreadNode, bool)>, SearchError> {
+    let Some(metadata) = backend
+        .get_chunk(&id.to_string())
+        .await?
+        .and_then(|chunk| chunk.metadata.flatten())
+    else {
+        return Ok(None);
+    };
+    // Stories are stored with a top_parent_id of -1.
+    let story_id = metadata["top_parent_id"]
+        .as_i64()
+        .filter(|top_parent_id| *top_parent_id > 0)
+        .unwrap_or(id);
+    let story_comments = fetch_comments(backend, story_id).await?;
+    Ok(Some((
+        build_thread(metadata, story_comments.comments),
+        story_comments.truncated,
+    )))
+}
+
+/// Hacker News item
+///
+/// A story or comment with all of the replies below it, threaded. Each comment can be
+/// collapsed without JS.
+#[utoipa::path(
+    get,
+    path = "/item/{id}",
+    tag = "search",
+    responses(
+        (status = 200, description = "HTML page of the item and its replies", body = String),
+        (status = 304, description = "The page is unchanged since the ETag in `If-None-Match`"),
+        (status = 404, description = "HTML page explaining that there is no such item", body = String),
+        (status = "5XX", description = "HTML page explaining that the item could not be loaded", body = String),
+    ),
+    params(
+        ("id" = i64, Path, description = "HN id of the story or comment")
+    )
+)]
+#[get("/item/{id}")]
+pub async fn item(
+    req: HttpRequest,
+    templates: Templates<'_>,
+    config: web::Data<Config>,
+    backend: web::Data<dyn SearchBackend>,
+    id: web::Path<i64>,
+) -> impl actix_web::Responder {
+    let templ = templates.get_template("item.html").unwrap();
+    let (thread, truncated, status, error) = match get_thread(backend.get_ref(), *id).await {
+        Ok(Some((thread, truncated))) => (Some(thread), truncated, StatusCode::OK, None),
+        Ok(None) => (
+            None,
+            false,
+            StatusCode::NOT_FOUND,
+            Some(format!("Item {} isn't in the index.", id)),
+        ),
+        Err(err) => {
+            tracing::error!(error = %err, kind = err.kind(), "Loading item {} failed", id);
+            (None, false, err.status_code(), Some(err.user_message()))
+        }
+    };
+
+    let response_body = match templ.render(context! {
+        num_comments => thread.as_ref().map(ThreadNode::num_descendants),
+        item => thread,
+        truncated => truncated,
+        max_comments => MAX_THREAD_COMMENTS,
+        error => error,
+    }) {
+        Ok(body) => body,
+        Err(err) => return render_failed(err),
+    };
+
+    match status {
+        StatusCode::OK => cacheable_html(&req, response_body, config.cache.ttl_secs),
+        status => HttpResponse::build(status)
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .body(response_body),
+    }
+}
@@ -1,2 +1,8 @@
+pub mod api_handler;
+pub mod item_handler;
 pub mod page_handler;
 pub mod search_handler;
+pub mod search_request;
+pub mod similar_handler;
+pub mod site_handler;
+pub mod user_handler;
@@ -1,10 +1,29 @@
-use crate::{handlers::search_handler::get_search_results, Templates};
-use actix_web::{get, web, HttpResponse};
+use crate::{
+    backend::SearchBackend,
+    config::{Config, SearchConfig},
+    errors::SearchError,
+    handlers::{
+        search_handler::get_search_results,
+        search_request::{SearchRequest, SearchType},
+    },
+    query::{
+        ast::{Filter, Node},
+        canonical::{canonicalize, serialize},
+        parse,
+    },
+    Templates,
+};
+use actix_web::{
+    get,
+    http::header::{self, CacheControl, CacheDirective, EntityTag, IfNoneMatch},
+    web, HttpMessage, HttpRequest, HttpResponse, ResponseError,
+};
 use minijinja::context;
 use serde::{Deserialize, Serialize};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use utoipa::ToSchema;
 
-#[derive(Debug, Deserialize, Serialize, ToSchema, Clone)]
+#[derive(Debug, Deserialize, Serialize, ToSchema, Clone, PartialEq)]
 pub struct SearchQueryParams {
     pub q: Option<String>,
     pub page: Option<i64>,
@@ -13,51 +32,307 @@ pub struct SearchQueryParams {
     pub search_type: Option<String>,
     pub post_type: Option<String>, // "all" | "story" | "show" | "job" | "poll"
 }
+
+/// Post types offered by the type dropdown, which an inline `type:` can be moved into.
+const FORM_POST_TYPES: [&str; 5] = ["story", "comment", "show", "job", "poll"];
+
+impl SearchQueryParams {
+    /// The canonical spelling of this search: `q` is normalized, params left at their
+    /// default in `config` are dropped and a lone inline `type:` moves into `post_type` (or
+    /// is dropped when it repeats it). Returns `None` when the query has diagnostics, as
+    /// rewriting it would silently drop the parts that were not understood.
+    pub fn canonical(&self, config: &SearchConfig) -> Option<SearchQueryParams> {
+        let mut post_type = self
+            .post_type
+            .as_deref()
+            .map(str::trim)
+            .filter(|post_type| !post_type.is_empty() && *post_type != "all")
+            .map(str::to_string);
+
+        let q = match &self.q {
+            Some(q) => {
+                let query = parse(q);
+                if !query.diagnostics.is_empty() {
+                    return None;
+                }
+                let mut clauses = canonicalize(&query.clauses);
+                let inline_types = clauses
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(idx, clause)| match &clause.node {
+                        Node::Filter(Filter::Type(post_types))
+                            if !clause.negated && post_types.len() == 1 =>
+                        {
+                            Some((idx, post_types[0].clone()))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<(usize, String)>>();
+                // Keep at least one clause so the search still runs.
+                if clauses.len() > 1 {
+                    match (&post_type, inline_types.as_slice()) {
+                        (None, [(idx, inline)]) if FORM_POST_TYPES.contains(&inline.as_str()) => {
+                            post_type = Some(inline.clone());
+                            clauses.remove(*idx);
+                        }
+                        (Some(post_type), [(idx, inline)]) if post_type == inline => {
+                            clauses.remove(*idx);
+                        }
+                        _ => {}
+                    }
+                }
+                Some(serialize(&clauses))
+            }
+            None => None,
+        };
+
+        Some(SearchQueryParams {
+            q,
+            page: self.page.filter(|page| *page != 1),
+            page_size: self
+                .page_size
+                .filter(|page_size| *page_size != config.default_page_size),
+            order_by: self
+                .order_by
+                .clone()
+                .filter(|order_by| !order_by.is_empty() && order_by != "relevance"),
+            search_type: self.search_type.clone().filter(|search_type| {
+                !search_type.is_empty()
+                    && search_type.parse::<SearchType>() != Ok(config.default_search_type)
+            }),
+            post_type,
+        })
+    }
+
+    /// Homepage URL for these params, leaving out the ones which aren't set.
+    pub fn url(&self) -> String {
+        let params = [
+            ("q", self.q.clone()),
+            ("page", self.page.map(|page| page.to_string())),
+            (
+                "page_size",
+                self.page_size.map(|page_size| page_size.to_string()),
+            ),
+            ("order_by", self.order_by.clone()),
+            ("search_type", self.search_type.clone()),
+            ("post_type", self.post_type.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect::<Vec<(&str, String)>>();
+
+        match serde_urlencoded::to_string(&params) {
+            Ok(query_string) if !query_string.is_empty() => format!("/?{}", query_string),
+            _ => "/".to_string(),
+        }
+    }
+}
+/// Pages linked on either side of the current one, besides the first and last page.
+const PAGER_WINDOW: i64 = 2;
+
+/// A numbered link of the pager, or a gap between two of them when `number` is `None`.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct PageLink {
+    pub number: Option<i64>,
+    pub url: Option<String>,
+    pub current: bool,
+}
+
+/// Links to the previous, next and nearby pages of a search, keeping all of its filters.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Pager {
+    pub prev: Option<String>,
+    pub next: Option<String>,
+    pub pages: Vec<PageLink>,
+}
+
+impl Pager {
+    /// Builds the pager for `params`. Without `total_pages` only prev/next links are
+    /// shown, with a next page assumed whenever this one came back with `page_size` results.
+    /// No link goes past `max_page`, which searches are rejected beyond.
+    pub fn new(
+        params: &SearchQueryParams,
+        page_size: i64,
+        max_page: i64,
+        total_pages: Option<i64>,
+        num_results: usize,
+    ) -> Pager {
+        let page = params.page.unwrap_or(1).max(1);
+        let page_url = |number: i64| {
+            SearchQueryParams {
+                page: Some(number).filter(|number| *number != 1),
+                ..params.clone()
+            }
+            .url()
+        };
+
+        let last_page = total_pages.map(|total_pages| total_pages.min(max_page));
+        let has_next = match last_page {
+            Some(last_page) => page < last_page,
+            None => num_results as i64 >= page_size && page < max_page,
+        };
+
+        let mut pages = vec![];
+        if let Some(last_page) = last_page.filter(|last_page| *last_page > 1) {
+            let window = (page - PAGER_WINDOW).max(2)..=(page + PAGER_WINDOW).min(last_page - 1);
+            let mut last_linked = 0;
+            for number in std::iter::once(1).chain(window).chain([last_page]) {
+                if number > last_linked + 1 {
+                    pages.push(PageLink {
+                        number: None,
+                        url: None,
+                        current: false,
+                    });
+                }
+                pages.push(PageLink {
+                    number: Some(number),
+                    url: Some(page_url(number)),
+                    current: number == page,
+                });
+                last_linked = number;
+            }
+        }
+
+        Pager {
+            prev: (page > 1).then(|| page_url(page - 1)),
+            next: has_next.then(|| page_url(page + 1)),
+            pages,
+        }
+    }
+}
+
+/// Responds with a 500 when a page fails to render, such as on a template error, rather
+/// than panicking the worker.
+pub fn render_failed(err: minijinja::Error) -> HttpResponse {
+    tracing::error!(error = %err, "Rendering a page failed");
+    HttpResponse::InternalServerError()
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .content_type("text/plain; charset=utf-8")
+        .body("The page could not be rendered.")
+}
+
+/// Responds with the rendered `body`, which browsers and proxies may keep for `max_age_secs`
+/// and revalidate with its ETag, answered with a 304 while the page is unchanged.
+pub fn cacheable_html(req: &HttpRequest, body: String, max_age_secs: u64) -> HttpResponse {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = EntityTag::new_strong(format!("{:016x}", hasher.finish()));
+    let cache_control = CacheControl(vec![
+        CacheDirective::Public,
+        CacheDirective::MaxAge(max_age_secs as u32),
+    ]);
+
+    let unchanged = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+    if unchanged {
+        return HttpResponse::NotModified()
+            .insert_header(header::ETag(etag))
+            .insert_header(cache_control)
+            .finish();
+    }
+    HttpResponse::Ok()
+        .insert_header(header::ETag(etag))
+        .insert_header(cache_control)
+        .content_type("text/html; charset=utf-8")
+        .body(body)
+}
+
 /// Search Hacker News
 ///
 /// Q query param is required for search and can include inline filters. Other query params are optional.
 #[utoipa::path(
     get,
-    path = "/search",
+    path = "/",
     tag = "search",
     responses(
         (status = 200, description = "HTML page with search results", body = String),
+        (status = 302, description = "Redirect to the canonical URL of the search"),
+        (status = 304, description = "The page is unchanged since the ETag in `If-None-Match`"),
+        (status = 400, description = "HTML page explaining which query param is invalid", body = String),
+        (status = "5XX", description = "HTML page explaining that search failed", body = String),
     ),
     params(
         ("q" = Option<String>, Query, description = "Search query with inline filters"),
-        ("page" = Option<i64>, Query, description = "Page number"),
-        ("page_size" = Option<i64>, Query, description = "Number of items per page"),
-        ("order_by" = Option<String>, Query, description = "Order by field"),
-        ("search_type" = Option<String>, Query, description = "`fulltext`, `semantic`, `hybrid`, or `keyword` for the search type")
+        ("page" = Option<i64>, Query, description = "Page number, from 1 to `MAX_PAGE` (1000 by default)"),
+        ("page_size" = Option<i64>, Query, description = "Number of items per page, at most `MAX_PAGE_SIZE` (100 by default)"),
+        ("order_by" = Option<String>, Query, description = "`relevance`, `points`, `date` or `comments`"),
+        ("search_type" = Option<String>, Query, description = "`fulltext`, `semantic`, `hybrid`, or `keyword` for the search type"),
+        ("post_type" = Option<String>, Query, description = "`all`, `story`, `comment`, `show`, `job` or `poll`")
     )
 )]
 #[get("/")]
 pub async fn homepage(
+    req: HttpRequest,
     templates: Templates<'_>,
-    trieve_client: web::Data<reqwest::Client>,
+    config: web::Data<Config>,
+    backend: web::Data<dyn SearchBackend>,
     query_params: web::Query<SearchQueryParams>,
 ) -> impl actix_web::Responder {
+    let request = SearchRequest::new(&query_params, &config.search);
+    let canonical = query_params
+        .canonical(&config.search)
+        .filter(|_| request.is_ok());
+    if let Some(canonical) = &canonical {
+        if canonical.q.as_deref().is_some_and(|q| !q.is_empty()) && *canonical != *query_params {
+            return HttpResponse::Found()
+                .insert_header((header::LOCATION, canonical.url()))
+                .finish();
+        }
+    }
+
     let templ = templates.get_template("homepage.html").unwrap();
-    let results =
-        if query_params.q.is_some() && !query_params.q.clone().unwrap_or_default().is_empty() {
-            get_search_results(trieve_client, query_params.clone()).await
-        } else {
-            vec![]
-        };
+    let (results, total_pages, diagnostics, error) = match &request {
+        Ok(request) if !request.q.is_empty() => {
+            match get_search_results(backend.get_ref(), &config, request, true).await {
+                Ok(search_resp) => (
+                    search_resp.chunks,
+                    search_resp.total_pages,
+                    search_resp.diagnostics,
+                    None,
+                ),
+                Err(err) => (vec![], None, vec![], Some(err)),
+            }
+        }
+        Ok(_) => (vec![], None, vec![], None),
+        Err(err) => (vec![], None, vec![], Some(err.clone())),
+    };
 
-    let response_body = if query_params.q.is_some() {
+    let response_body = if query_params.q.is_some() || error.is_some() {
+        let canonical = canonical.unwrap_or_else(|| query_params.clone().into_inner());
+        let page_size = request
+            .as_ref()
+            .map_or(config.search.default_page_size, |request| request.page_size);
+        let pager = Pager::new(
+            &canonical,
+            page_size,
+            config.search.max_page,
+            total_pages,
+            results.len(),
+        );
         templ
             .render(context! {
                 results => results,
+                diagnostics => diagnostics,
+                error => error.as_ref().map(SearchError::user_message),
                 filter => query_params.clone().into_inner(),
                 query => query_params.q.clone().unwrap_or_default(),
+                pager => pager,
+                canonical_url => canonical.url(),
             })
             .expect("Should always render")
     } else {
         templ.render(context! {}).expect("Should always render")
     };
 
-    HttpResponse::Ok().body(response_body)
+    match error {
+        Some(err) => HttpResponse::build(err.status_code())
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .body(response_body),
+        None => cacheable_html(&req, response_body, config.cache.ttl_secs),
+    }
 }
 
 #[get("/about")]
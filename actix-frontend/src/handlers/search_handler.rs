@@ -1,11 +1,20 @@
-use super::page_handler::SearchQueryParams;
-use actix_web::web;
-use chrono::{NaiveDate, Utc};
-use regex::Regex;
+use super::search_request::{PostType, SearchRequest, SimilarRequest};
+use crate::{
+    backend::{RecommendChunksPayload, SearchBackend},
+    config::Config,
+    errors::SearchError,
+    highlight::{excerpt, query_terms, snippets},
+    query::{
+        diagnostics::Diagnostic,
+        lower::{lower, lower_at},
+        parse,
+    },
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use trieve_client::models::{
-    self, ChunkMetadata, ConditionType, FieldCondition, HasIdCondition, HighlightOptions,
-    MatchCondition, SortOrder,
+    self, ChunkMetadata, FieldCondition, HasIdCondition, HighlightOptions, MatchCondition,
+    SortOrder,
 };
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -13,18 +22,67 @@ pub struct ScoreChunkMetadata {
     pub chunk: ChunkMetadata,
     pub highlights: Option<Option<Vec<String>>>,
     pub score: f32,
+    /// Escaped HTML of the text around the query's words, with them in `<mark>`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub snippets: Vec<String>,
+    /// The comment a comment replies to, unless it replies to the story.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<ParentComment>,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Enough of a comment to show what a reply to it is about.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ParentComment {
+    pub id: i64,
+    pub by: String,
+    /// Start of the text of the comment, as plain text.
+    pub excerpt: String,
+}
+
+/// Characters of a parent comment shown with the replies to it.
+const PARENT_EXCERPT_CHARS: usize = 160;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
 pub struct SimplifiedSearchResponse {
     pub chunks: Vec<ScoreChunkMetadata>,
     pub total_pages: Option<i64>,
+    /// Parts of the query which were not applied as written.
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    /// What the inline filters were parsed into, echoed back by the JSON API.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parsed_query: Option<ParsedQuery>,
+}
+
+/// The text query and filters which were sent to Trieve.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ParsedQuery {
+    pub query: String,
+    pub filters: CustomChunkFilter,
 }
 
 pub struct CleanedQueriesAndSearchFilters {
     pub cleaned_query: String,
-    pub must_filters: Vec<ConditionType>,
-    pub must_not_filters: Vec<ConditionType>,
+    pub must_filters: Vec<CustomConditionType>,
+    pub must_not_filters: Vec<CustomConditionType>,
+    pub should_filters: Vec<CustomConditionType>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CleanedQueriesAndSearchFilters {
+    /// The `filters` sent to Trieve with the search request.
+    pub fn chunk_filter(&self) -> CustomChunkFilter {
+        CustomChunkFilter {
+            must: Some(Some(self.must_filters.clone())),
+            must_not: Some(Some(self.must_not_filters.clone())),
+            jsonb_prefilter: Some(Some(false)),
+            should: if self.should_filters.is_empty() {
+                None
+            } else {
+                Some(Some(self.should_filters.clone()))
+            },
+        }
+    }
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
@@ -32,6 +90,78 @@ pub struct TypoOptions {
     pub correct_typos: Option<Option<bool>>,
 }
 
+/// Same as `models::ConditionType`, plus the nested filter Trieve accepts in place of a
+/// condition which the generated client is missing.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CustomConditionType {
+    FieldCondition(Box<FieldCondition>),
+    ChunkFilter(Box<CustomChunkFilter>),
+    HasIdCondition(Box<HasIdCondition>),
+}
+
+impl CustomConditionType {
+    /// Condition on `field` having `value`, such as a metadata field of the items.
+    pub fn field_is(field: &str, value: MatchCondition) -> CustomConditionType {
+        CustomConditionType::FieldCondition(Box::new(FieldCondition {
+            field: field.to_string(),
+            match_any: None,
+            match_all: Some(Some(vec![value])),
+            date_range: None,
+            geo_bounding_box: None,
+            geo_polygon: None,
+            geo_radius: None,
+            range: None,
+        }))
+    }
+}
+
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomChunkFilter {
+    /// JSONB prefilter tells the database to perform a pre-filter on the JSONB metadata field.
+    #[serde(
+        rename = "jsonb_prefilter",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub jsonb_prefilter: Option<Option<bool>>,
+    /// All of these field conditions have to match for the chunk to be included in the result set.
+    #[serde(rename = "must", default, skip_serializing_if = "Option::is_none")]
+    pub must: Option<Option<Vec<CustomConditionType>>>,
+    /// None of these field conditions can match for the chunk to be included in the result set.
+    #[serde(rename = "must_not", default, skip_serializing_if = "Option::is_none")]
+    pub must_not: Option<Option<Vec<CustomConditionType>>>,
+    /// Only one of these field conditions has to match for the chunk to be included in the result set.
+    #[serde(rename = "should", default, skip_serializing_if = "Option::is_none")]
+    pub should: Option<Option<Vec<CustomConditionType>>>,
+}
+
+impl CustomChunkFilter {
+    /// Filter on the chunks with any of `tags` in their `tag_set`, such as an author or a site.
+    pub fn tagged(tags: &[String]) -> CustomChunkFilter {
+        CustomChunkFilter {
+            must: Some(Some(vec![CustomConditionType::FieldCondition(Box::new(
+                FieldCondition {
+                    field: "tag_set".to_string(),
+                    match_any: Some(Some(
+                        tags.iter()
+                            .map(|tag| MatchCondition::String(tag.clone()))
+                            .collect(),
+                    )),
+                    match_all: None,
+                    date_range: None,
+                    geo_bounding_box: None,
+                    geo_polygon: None,
+                    geo_radius: None,
+                    range: None,
+                },
+            ))])),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
 pub struct CustomSearchChunksReqPayload {
     /// Set content_only to true to only returning the chunk_html of the chunks. This is useful for when you want to reduce amount of data over the wire for latency improvement (typically 10-50ms). Default is false.
@@ -42,7 +172,7 @@ pub struct CustomSearchChunksReqPayload {
     )]
     pub content_only: Option<Option<bool>>,
     #[serde(rename = "filters", default, skip_serializing_if = "Option::is_none")]
-    pub filters: Option<Option<Box<models::ChunkFilter>>>,
+    pub filters: Option<Option<Box<CustomChunkFilter>>>,
     /// Get total page count for the query accounting for the applied filters. Defaults to false, but can be set to true when the latency penalty is acceptable (typically 50-200ms).
     #[serde(
         rename = "get_total_pages",
@@ -106,408 +236,196 @@ pub struct CustomSearchChunksReqPayload {
     pub user_id: Option<Option<String>>,
 }
 
-pub fn get_default_score_threshold(search_method: models::SearchMethod) -> f32 {
-    match search_method {
-        models::SearchMethod::Fulltext => 5.0,
-        models::SearchMethod::Semantic => 0.5,
-        models::SearchMethod::Hybrid => 0.01,
-        models::SearchMethod::Bm25 => 1.5,
+/// Adds the snippets of comments and text posts, whose whole text is too long to show.
+fn add_snippets(result: &mut ScoreChunkMetadata, terms: &[String], window: usize) {
+    let metadata = result.chunk.metadata.clone().flatten().unwrap_or_default();
+    let text = metadata["text"].as_str().unwrap_or_default();
+    if text.is_empty() {
+        return;
     }
+    let highlights = result.highlights.clone().flatten().unwrap_or_default();
+    result.snippets = snippets(&highlights, text, terms, window);
 }
 
-pub fn parse_search_payload_params(query: String) -> CleanedQueriesAndSearchFilters {
-    let mut cleaned_query = query.clone();
-    let mut must_filters: Vec<ConditionType> = vec![];
-    let mut must_not_filters: Vec<ConditionType> = vec![];
+/// Id of the comment `result` replies to, `None` for stories and replies to stories.
+fn parent_comment_id(result: &ScoreChunkMetadata) -> Option<i64> {
+    let metadata = result.chunk.metadata.clone().flatten()?;
+    let parent = metadata["parent"].as_i64()?;
+    (metadata["type"] == "comment" && metadata["top_parent_id"].as_i64() != Some(parent))
+        .then_some(parent)
+}
 
-    // Process author filters
-    let any_author_names_regex = Regex::new(r"by:[a-zA-Z0-9_.][\w.-]+").unwrap();
-    let none_author_names_regex = Regex::new(r"by:-[\w.-]+").unwrap();
-    let any_author_matches = any_author_names_regex
-        .find_iter(&query)
-        .map(|m| m.as_str())
-        .collect::<Vec<&str>>();
-    let none_author_matches = none_author_names_regex
-        .find_iter(&query)
-        .map(|m| m.as_str())
-        .collect::<Vec<&str>>();
-    if !any_author_matches.is_empty() {
-        must_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "tag_set".to_string(),
-            match_any: Some(Some(
-                any_author_matches
-                    .iter()
-                    .map(|author| {
-                        cleaned_query = cleaned_query.replace(author, "");
-                        let author = author.replace("by:", "");
-                        MatchCondition::String(author.to_string())
-                    })
-                    .collect::<Vec<MatchCondition>>(),
-            )),
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: None,
-            range: None,
-        })));
-    }
-    if !none_author_matches.is_empty() {
-        must_not_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "tag_set".to_string(),
-            match_any: Some(Some(
-                none_author_matches
-                    .iter()
-                    .map(|author| {
-                        cleaned_query = cleaned_query.replace(author, "");
-                        let author = author.replace("by:-", "");
-                        MatchCondition::String(author.to_string())
-                    })
-                    .collect::<Vec<MatchCondition>>(),
-            )),
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: None,
-            range: None,
-        })));
+/// Adds the parent comment of replies, fetched in one call. The results are still shown
+/// without them when that call fails.
+pub async fn add_parent_comments(backend: &dyn SearchBackend, results: &mut [ScoreChunkMetadata]) {
+    let mut parent_ids = results
+        .iter()
+        .filter_map(parent_comment_id)
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>();
+    parent_ids.sort();
+    parent_ids.dedup();
+    if parent_ids.is_empty() {
+        return;
     }
 
-    // Process site filters
-    let any_site_regex = Regex::new(r"site:[a-zA-Z0-9_.][\w.-]+").unwrap();
-    let none_site_regex = Regex::new(r"site:-[\w.-]+").unwrap();
-    let any_site_matches = any_site_regex
-        .find_iter(&query)
-        .map(|m| m.as_str())
-        .collect::<Vec<&str>>();
-    let none_site_matches = none_site_regex
-        .find_iter(&query)
-        .map(|m| m.as_str())
-        .collect::<Vec<&str>>();
-    if !any_site_matches.is_empty() {
-        must_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "tag_set".to_string(),
-            match_any: Some(Some(
-                any_site_matches
-                    .iter()
-                    .map(|site| {
-                        cleaned_query = cleaned_query.replace(site, "");
-                        let site = site.replace("site:", "");
-                        MatchCondition::String(site.to_string())
-                    })
-                    .collect::<Vec<MatchCondition>>(),
-            )),
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: None,
-            range: None,
-        })));
-    }
-    if !none_site_matches.is_empty() {
-        must_not_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "tag_set".to_string(),
-            match_any: Some(Some(
-                none_site_matches
-                    .iter()
-                    .map(|site| {
-                        cleaned_query = cleaned_query.replace(site, "");
-                        let site = site.replace("site:-", "");
-                        MatchCondition::String(site.to_string())
-                    })
-                    .collect::<Vec<MatchCondition>>(),
-            )),
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: None,
-            range: None,
-        })));
-    }
-
-    // Process points filters
-    let points_gt_regex = Regex::new(r"points>\d+").unwrap();
-    let points_lt_regex = Regex::new(r"points<\d+").unwrap();
-    let points_gt_match = points_gt_regex.find(&query).map(|m| {
-        cleaned_query = cleaned_query.replace(m.as_str(), "");
-        let m = m.as_str().replace("points>", "");
-        m.as_str().parse::<i64>().ok()
-    });
-    let points_lt_match = points_lt_regex.find(&query).map(|m| {
-        cleaned_query = cleaned_query.replace(m.as_str(), "");
-        let m = m.as_str().replace("points<", "");
-        m.as_str().parse::<i64>().ok()
-    });
-    if points_gt_match.is_some() || points_lt_match.is_some() {
-        must_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "num_value".to_string(),
-            match_any: None,
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: None,
-            range: Some(Some(Box::new(models::Range {
-                gt: points_gt_match.map(|m| {
-                    m.map(|parsed_int| Box::new(models::RangeCondition::Integer(parsed_int)))
-                }),
-                lt: points_lt_match.map(|m| {
-                    m.map(|parsed_int| Box::new(models::RangeCondition::Integer(parsed_int)))
-                }),
-                gte: None,
-                lte: None,
-            }))),
-        })));
-    };
-
-    let comment_count_gt_regex = Regex::new(r"comments>\d+").unwrap();
-    let comment_count_lt_regex = Regex::new(r"comments<\d+").unwrap();
-    let comments_gt_match = comment_count_gt_regex.find(&query).map(|m| {
-        cleaned_query = cleaned_query.replace(m.as_str(), "");
-        let m = m.as_str().replace("comments>", "");
-        m.as_str().parse::<i64>().ok()
-    });
-    let comments_lt_match = comment_count_lt_regex.find(&query).map(|m| {
-        cleaned_query = cleaned_query.replace(m.as_str(), "");
-        let m = m.as_str().replace("comments<", "");
-        m.as_str().parse::<i64>().ok()
-    });
-    if comments_gt_match.is_some() || comments_lt_match.is_some() {
-        must_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "metadata.descendants".to_string(),
-            match_any: None,
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: None,
-            range: Some(Some(Box::new(models::Range {
-                gt: comments_gt_match.map(|m| {
-                    m.map(|parsed_int| Box::new(models::RangeCondition::Integer(parsed_int)))
-                }),
-                lt: comments_lt_match.map(|m| {
-                    m.map(|parsed_int| Box::new(models::RangeCondition::Integer(parsed_int)))
-                }),
-                gte: None,
-                lte: None,
-            }))),
-        })));
+    let parents = match backend.get_chunks(&parent_ids).await {
+        Ok(parents) => parents,
+        Err(err) => {
+            tracing::warn!(error = %err, "Could not fetch the parents of comments");
+            return;
+        }
     };
+    let parents = parents
+        .into_iter()
+        .filter_map(|chunk| chunk.metadata.flatten())
+        .filter_map(|metadata| Some((metadata["id"].as_i64()?, metadata)))
+        .collect::<HashMap<i64, serde_json::Value>>();
+    for result in results.iter_mut() {
+        let Some((id, parent)) =
+            parent_comment_id(result).and_then(|id| parents.get(&id).map(|parent| (id, parent)))
+        else {
+            continue;
+        };
+        result.parent = Some(ParentComment {
+            id,
+            by: parent["by"].as_str().unwrap_or_default().to_string(),
+            excerpt: excerpt(
+                parent["text"].as_str().unwrap_or_default(),
+                PARENT_EXCERPT_CHARS,
+            ),
+        });
+    }
+}
 
-    let date_gt_regex = Regex::new(r"date>\d{2}-\d{2}-\d{4}").unwrap();
-    let date_lt_regex = Regex::new(r"date<\d{2}-\d{2}-\d{4}").unwrap();
-    let timestamp_lt_match = date_lt_regex
-        .find(&query)
-        .map(|m| {
-            cleaned_query = cleaned_query.replace(m.as_str(), "");
-            let m = m.as_str().replace("date<", "");
-            let date_parts = m.as_str().split("-").collect::<Vec<&str>>();
-            let (month, day, year) = (
-                date_parts.first().map(|m| m.parse::<u32>().ok()),
-                date_parts.get(1).map(|m| m.parse::<u32>().ok()),
-                date_parts.get(2).map(|m| m.parse::<i32>().ok()),
-            );
-
-            match (month, day, year) {
-                (Some(Some(month)), Some(Some(day)), Some(Some(year))) => {
-                    NaiveDate::from_ymd_opt(year, month, day).map(|date| {
-                        date.and_hms_milli_opt(0, 0, 0, 0)
-                            .expect("0 offset always valid")
-                            .and_local_timezone(Utc)
-                            .unwrap()
-                            .timestamp()
-                    })
-                }
-                _ => None,
-            }
-        })
-        .unwrap_or(None);
-    let timestamp_gt_match = date_gt_regex
-        .find(&query)
-        .map(|m| {
-            cleaned_query = cleaned_query.replace(m.as_str(), "");
-            let m = m.as_str().replace("date>", "");
-            let date_parts = m.as_str().split("-").collect::<Vec<&str>>();
-            let (month, day, year) = (
-                date_parts.first().map(|m| m.parse::<u32>().ok()),
-                date_parts.get(1).map(|m| m.parse::<u32>().ok()),
-                date_parts.get(2).map(|m| m.parse::<i32>().ok()),
-            );
+/// Parses the inline search syntax (`by:`, `site:`, `points>`, ...) out of the raw query.
+pub fn parse_search_payload_params(query: String) -> CleanedQueriesAndSearchFilters {
+    lower(&parse(&query))
+}
 
-            match (month, day, year) {
-                (Some(Some(month)), Some(Some(day)), Some(Some(year))) => {
-                    NaiveDate::from_ymd_opt(year, month, day).map(|date| {
-                        date.and_hms_milli_opt(0, 0, 0, 0)
-                            .expect("0 offset always valid")
-                            .and_local_timezone(Utc)
-                            .unwrap()
-                            .timestamp()
-                    })
-                }
-                _ => None,
-            }
-        })
-        .unwrap_or(None);
-    if timestamp_lt_match.is_some() || timestamp_gt_match.is_some() {
-        must_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "time_stamp".to_string(),
-            match_any: None,
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: None,
-            range: Some(Some(Box::new(models::Range {
-                gt: Some(
-                    timestamp_gt_match
-                        .map(|parsed_int| Box::new(models::RangeCondition::Integer(parsed_int))),
-                ),
-                lt: Some(
-                    timestamp_lt_match
-                        .map(|parsed_int| Box::new(models::RangeCondition::Integer(parsed_int))),
-                ),
-                gte: None,
-                lte: None,
-            }))),
-        })));
-    }
+/// Start of the cache period of `ttl_secs` which `now` falls in. Relative dates count back
+/// from it rather than from the current second, so a search such as `date>7d` sends the
+/// same payload, and hits the cache, for the whole period.
+pub fn cache_period_start(now: i64, ttl_secs: u64) -> i64 {
+    now - now.rem_euclid(ttl_secs.max(1) as i64)
+}
 
-    let story_id_regex = Regex::new(r"story:\d+").unwrap();
-    let story_id_match = story_id_regex.find(&query).map(|m| m.as_str());
-    if let Some(story_id) = story_id_match {
-        cleaned_query = cleaned_query.replace(story_id, "");
-        let story_id = story_id.replace("story:", "");
+/// Parses the query with its relative dates counted from the start of the cache period.
+fn parse_for_search(query: &str, config: &Config) -> CleanedQueriesAndSearchFilters {
+    let now = cache_period_start(chrono::Utc::now().timestamp(), config.cache.ttl_secs);
+    lower_at(&parse(query), now)
+}
 
-        must_filters.push(ConditionType::HasIdCondition(Box::new(HasIdCondition {
-            ids: None,
-            tracking_ids: Some(Some(vec![story_id.to_string()])),
-        })));
-    }
+/// Condition on the tag of `post_type`, `None` for all post types.
+fn post_type_condition(post_type: PostType) -> Option<CustomConditionType> {
+    post_type.tag().map(|tag| {
+        CustomConditionType::field_is("tag_set", MatchCondition::String(tag.to_string()))
+    })
+}
 
-    let type_regex = Regex::new(r"type:[\w.-]+").unwrap();
-    let type_match = type_regex.find(&query).map(|m| m.as_str());
-    if let Some(story_type) = type_match {
-        cleaned_query = cleaned_query.replace(story_type, "");
-        let story_type = story_type.replace("type:", "");
+/// Items similar to the one of the request and unlike its negative examples. Only the inline
+/// filters of `q` apply, the words of the query are left out.
+pub async fn get_recommendations(
+    backend: &dyn SearchBackend,
+    config: &Config,
+    request: &SimilarRequest,
+) -> Result<SimplifiedSearchResponse, SearchError> {
+    let mut parsed_query = parse_for_search(&request.q, config);
+    let diagnostics = std::mem::take(&mut parsed_query.diagnostics);
+    parsed_query
+        .must_filters
+        .extend(post_type_condition(request.post_type));
+    let echo = ParsedQuery {
+        query: parsed_query.cleaned_query.clone(),
+        filters: parsed_query.chunk_filter(),
+    };
 
-        must_filters.push(ConditionType::FieldCondition(Box::new(FieldCondition {
-            field: "tag_set".to_string(),
-            match_any: None,
-            date_range: None,
-            geo_bounding_box: None,
-            geo_polygon: None,
-            geo_radius: None,
-            match_all: Some(Some(vec![MatchCondition::String(story_type.to_string())])),
-            range: None,
-        })));
-    }
+    let mut chunks = backend
+        .recommend(&RecommendChunksPayload {
+            positive_tracking_ids: vec![request.id.to_string()],
+            negative_tracking_ids: request.negative.iter().map(i64::to_string).collect(),
+            filters: Some(echo.filters.clone()),
+            limit: request.limit,
+        })
+        .await?;
+    add_parent_comments(backend, &mut chunks).await;
 
-    CleanedQueriesAndSearchFilters {
-        must_filters,
-        must_not_filters,
-        cleaned_query: cleaned_query.trim().to_string(),
-    }
+    Ok(SimplifiedSearchResponse {
+        chunks,
+        total_pages: None,
+        diagnostics,
+        parsed_query: Some(echo),
+    })
 }
 
+/// Runs the search against the backend. `get_total_pages` asks Trieve to count the pages of
+/// the search, which costs it an extra 50-200ms.
 pub async fn get_search_results(
-    trieve_client: web::Data<reqwest::Client>,
-    query_params: web::Query<SearchQueryParams>,
-) -> Vec<ScoreChunkMetadata> {
-    let dataset_id =
-        std::env::var("TRIEVE_DATASET_ID").expect("TRIEVE_DATASET_ID env must be present");
-    let trieve_api_url = std::env::var("TRIEVE_API_URL").expect("TRIEVE_API_URL must be set");
-    let trieve_api_key = std::env::var("TRIEVE_API_KEY").expect("TRIEVE_API_KEY must be set");
+    backend: &dyn SearchBackend,
+    config: &Config,
+    request: &SearchRequest,
+    get_total_pages: bool,
+) -> Result<SimplifiedSearchResponse, SearchError> {
+    run_search(backend, config, request, get_total_pages)
+        .await
+        .inspect_err(|err| tracing::error!(error = %err, kind = err.kind(), "Search failed"))
+}
 
-    let mut parsed_query =
-        parse_search_payload_params(query_params.q.clone().unwrap_or("hackernews".to_string()));
-    let search_method = match query_params.search_type.clone() {
-        Some(search_type) => match search_type.as_str() {
-            "fulltext" => models::SearchMethod::Fulltext,
-            "semantic" => models::SearchMethod::Semantic,
-            "hybrid" => models::SearchMethod::Hybrid,
-            "keyword" => models::SearchMethod::Bm25,
-            _ => models::SearchMethod::Fulltext,
-        },
-        _ => models::SearchMethod::Fulltext,
-    };
-    let score_threshold = get_default_score_threshold(search_method);
+async fn run_search(
+    backend: &dyn SearchBackend,
+    config: &Config,
+    request: &SearchRequest,
+    get_total_pages: bool,
+) -> Result<SimplifiedSearchResponse, SearchError> {
+    let mut parsed_query = parse_for_search(&request.q, config);
+    let search_method = request.search_type.search_method();
+    let score_threshold = config.search.score_thresholds.get(request.search_type);
+    let highlight = &config.search.highlight;
+    let diagnostics = std::mem::take(&mut parsed_query.diagnostics);
 
-    if let Some(post_type) = query_params.post_type.clone() {
-        if post_type != "all" {
-            parsed_query
-                .must_filters
-                .push(ConditionType::FieldCondition(Box::new(FieldCondition {
-                    field: "tag_set".to_string(),
-                    match_any: None,
-                    match_all: Some(Some(vec![MatchCondition::String(post_type)])),
-                    date_range: None,
-                    geo_bounding_box: None,
-                    geo_polygon: None,
-                    geo_radius: None,
-                    range: None,
-                })));
-        }
-    }
+    parsed_query
+        .must_filters
+        .extend(post_type_condition(request.post_type));
+
+    let echo = ParsedQuery {
+        query: parsed_query.cleaned_query.clone(),
+        filters: parsed_query.chunk_filter(),
+    };
 
     let search_req_payload = CustomSearchChunksReqPayload {
         content_only: None,
-        filters: Some(Some(Box::new(models::ChunkFilter {
-            must: Some(Some(parsed_query.must_filters)),
-            must_not: Some(Some(parsed_query.must_not_filters)),
-            jsonb_prefilter: Some(Some(false)),
-            should: None,
-        }))),
-        get_total_pages: None,
+        filters: Some(Some(Box::new(echo.filters.clone()))),
+        get_total_pages: Some(Some(get_total_pages)),
         highlight_options: Some(Some(Box::new(HighlightOptions {
             highlight_results: Some(Some(true)),
-            highlight_strategy: Some(Some(models::HighlightStrategy::Exactmatch)),
-            highlight_delimiters: Some(Some(vec![
-                " ".to_string(),
-                "-".to_string(),
-                "_".to_string(),
-                ".".to_string(),
-                ",".to_string(),
-            ])),
-            highlight_threshold: Some(Some(0.85)),
-            highlight_max_num: Some(Some(50)),
-            highlight_window: Some(Some(0)),
-            highlight_max_length: Some(Some(50)),
+            highlight_strategy: Some(Some(highlight.strategy.trieve())),
+            highlight_delimiters: Some(Some(highlight.delimiters.clone())),
+            highlight_threshold: Some(Some(highlight.threshold)),
+            highlight_max_num: Some(Some(highlight.max_num)),
+            highlight_window: Some(Some(highlight.window)),
+            highlight_max_length: Some(Some(highlight.max_length)),
         }))),
-        page: Some(Some(query_params.page.unwrap_or(1))),
-        page_size: Some(Some(query_params.page_size.unwrap_or(30))),
+        page: Some(Some(request.page)),
+        page_size: Some(Some(request.page_size)),
         query: Box::new(models::QueryTypes::String(parsed_query.cleaned_query)),
         remove_stop_words: None,
         score_threshold: Some(Some(score_threshold)),
         search_type: search_method,
         slim_chunks: None,
-        sort_options: if !query_params.order_by.clone().unwrap_or_default().is_empty()
-            && query_params.order_by.clone().unwrap_or_default() != "relevance"
-        {
-            query_params.order_by.clone().map(|order_by| {
-                Some(Box::new(models::SortOptions {
-                    location_bias: None,
-                    sort_by: Some(Some(Box::new(models::QdrantSortBy::SortByField(Box::new(
-                        models::SortByField {
-                            field: match order_by.as_str() {
-                                "points" => "num_value".to_string(),
-                                "comments" => "metadata.descendants".to_string(),
-                                "date" => "time_stamp".to_string(),
-                                _ => "num_value".to_string(),
-                            },
-                            direction: Some(Some(SortOrder::Desc)),
-                            prefetch_amount: None,
-                        },
-                    ))))),
-                    tag_weights: None,
-                    use_weights: None,
-                }))
-            })
-        } else {
-            None
-        },
+        sort_options: request.order_by.sort_field().map(|field| {
+            Some(Box::new(models::SortOptions {
+                location_bias: None,
+                sort_by: Some(Some(Box::new(models::QdrantSortBy::SortByField(Box::new(
+                    models::SortByField {
+                        field: field.to_string(),
+                        direction: Some(Some(SortOrder::Desc)),
+                        prefetch_amount: None,
+                    },
+                ))))),
+                tag_weights: None,
+                use_weights: None,
+            }))
+        }),
         typo_options: Some(TypoOptions {
             correct_typos: Some(Some(true)),
         }),
@@ -515,45 +433,16 @@ pub async fn get_search_results(
         user_id: None,
     };
 
-    let mut header_map: reqwest::header::HeaderMap = reqwest::header::HeaderMap::new();
-    header_map.insert(
-        "Content-Type",
-        reqwest::header::HeaderValue::from_static("application/json"),
-    );
-    header_map.insert(
-        "Authorization",
-        reqwest::header::HeaderValue::from_str(&trieve_api_key).unwrap(),
-    );
-    header_map.insert(
-        "TR-Dataset",
-        reqwest::header::HeaderValue::from_str(&dataset_id).unwrap(),
-    );
-    header_map.insert(
-        "X-API-Version",
-        reqwest::header::HeaderValue::from_static("V2"),
-    );
-
-    let search_req_resp = trieve_client
-        .post(trieve_api_url + "/api/chunk/search")
-        .headers(header_map)
-        .body(serde_json::to_string(&search_req_payload).unwrap())
-        .send()
-        .await;
-
-    match search_req_resp {
-        Ok(resp) => {
-            let resp_text = resp.text().await.unwrap();
-            match serde_json::from_str::<SimplifiedSearchResponse>(&resp_text) {
-                Ok(simple_search_resp) => simple_search_resp.chunks,
-                e => {
-                    println!("Error: {:?}", e);
-                    vec![]
-                }
-            }
-        }
-        Err(e) => {
-            println!("Error parsing search results: {:?}", e);
-            vec![]
-        }
+    let mut simple_search_resp = backend.search(&search_req_payload).await?;
+    let terms = query_terms(&echo.query);
+    for chunk in simple_search_resp.chunks.iter_mut() {
+        add_snippets(chunk, &terms, config.search.snippet_window);
     }
+    add_parent_comments(backend, &mut simple_search_resp.chunks).await;
+
+    Ok(SimplifiedSearchResponse {
+        diagnostics,
+        parsed_query: Some(echo),
+        ..simple_search_resp
+    })
 }
@@ -0,0 +1,232 @@
+use super::{page_handler::SearchQueryParams, similar_handler::SimilarQueryParams};
+use crate::{config::SearchConfig, errors::SearchError};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use trieve_client::models;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchType {
+    #[default]
+    Fulltext,
+    Semantic,
+    Hybrid,
+    Keyword,
+}
+
+impl SearchType {
+    pub fn search_method(self) -> models::SearchMethod {
+        match self {
+            SearchType::Fulltext => models::SearchMethod::Fulltext,
+            SearchType::Semantic => models::SearchMethod::Semantic,
+            SearchType::Hybrid => models::SearchMethod::Hybrid,
+            SearchType::Keyword => models::SearchMethod::Bm25,
+        }
+    }
+}
+
+impl FromStr for SearchType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fulltext" => Ok(SearchType::Fulltext),
+            "semantic" => Ok(SearchType::Semantic),
+            "hybrid" => Ok(SearchType::Hybrid),
+            "keyword" => Ok(SearchType::Keyword),
+            _ => Err(format!(
+                "search_type must be fulltext, semantic, hybrid or keyword, not `{}`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderBy {
+    #[default]
+    Relevance,
+    Points,
+    Date,
+    Comments,
+}
+
+impl OrderBy {
+    /// Field Trieve sorts on, `None` to keep its relevance ranking.
+    pub fn sort_field(self) -> Option<&'static str> {
+        match self {
+            OrderBy::Relevance => None,
+            OrderBy::Points => Some("num_value"),
+            OrderBy::Date => Some("time_stamp"),
+            OrderBy::Comments => Some("metadata.descendants"),
+        }
+    }
+}
+
+impl FromStr for OrderBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "relevance" => Ok(OrderBy::Relevance),
+            "points" => Ok(OrderBy::Points),
+            "date" => Ok(OrderBy::Date),
+            "comments" => Ok(OrderBy::Comments),
+            _ => Err(format!(
+                "order_by must be relevance, points, date or comments, not `{}`",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostType {
+    #[default]
+    All,
+    Story,
+    Comment,
+    Show,
+    Job,
+    Poll,
+}
+
+impl PostType {
+    /// Tag the chunks of this post type have in their `tag_set`, `None` for all of them.
+    pub fn tag(self) -> Option<&'static str> {
+        match self {
+            PostType::All => None,
+            PostType::Story => Some("story"),
+            PostType::Comment => Some("comment"),
+            PostType::Show => Some("show"),
+            PostType::Job => Some("job"),
+            PostType::Poll => Some("poll"),
+        }
+    }
+}
+
+impl FromStr for PostType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(PostType::All),
+            "story" => Ok(PostType::Story),
+            "comment" => Ok(PostType::Comment),
+            "show" => Ok(PostType::Show),
+            "job" => Ok(PostType::Job),
+            "poll" => Ok(PostType::Poll),
+            _ => Err(format!(
+                "post_type must be all, story, comment, show, job or poll, not `{}`",
+                s
+            )),
+        }
+    }
+}
+
+/// `SearchQueryParams` once checked: pages are within the configured bounds and the
+/// dropdown values are ones the search understands. Missing or empty params take their
+/// defaults.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchRequest {
+    pub q: String,
+    pub page: i64,
+    pub page_size: i64,
+    pub order_by: OrderBy,
+    pub search_type: SearchType,
+    pub post_type: PostType,
+}
+
+fn parse_param<T: FromStr<Err = String>>(value: &Option<String>, default: T) -> Result<T, String> {
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(default),
+        Some(value) => value.parse(),
+    }
+}
+
+impl SearchRequest {
+    pub fn new(params: &SearchQueryParams, config: &SearchConfig) -> Result<Self, SearchError> {
+        let page = params.page.unwrap_or(1);
+        if !(1..=config.max_page).contains(&page) {
+            return Err(SearchError::InvalidParams(format!(
+                "page must be between 1 and {}, not {}",
+                config.max_page, page
+            )));
+        }
+        let page_size = params.page_size.unwrap_or(config.default_page_size);
+        if page_size < 1 {
+            return Err(SearchError::InvalidParams(format!(
+                "page_size must be at least 1, not {}",
+                page_size
+            )));
+        }
+
+        Ok(SearchRequest {
+            q: params.q.clone().unwrap_or_default(),
+            page,
+            page_size: page_size.min(config.max_page_size),
+            order_by: parse_param(&params.order_by, OrderBy::default())
+                .map_err(SearchError::InvalidParams)?,
+            search_type: parse_param(&params.search_type, config.default_search_type)
+                .map_err(SearchError::InvalidParams)?,
+            post_type: parse_param(&params.post_type, PostType::default())
+                .map_err(SearchError::InvalidParams)?,
+        })
+    }
+}
+
+/// `SimilarQueryParams` once checked, along with the id of the item to find similar ones of.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SimilarRequest {
+    pub id: i64,
+    pub q: String,
+    pub post_type: PostType,
+    /// Items the results should be unlike, the item itself left out.
+    pub negative: Vec<i64>,
+    pub limit: i64,
+}
+
+impl SimilarRequest {
+    pub fn new(
+        id: i64,
+        params: &SimilarQueryParams,
+        config: &SearchConfig,
+    ) -> Result<Self, SearchError> {
+        let mut negative = vec![];
+        for negative_id in params
+            .negative
+            .iter()
+            .flat_map(|negative| negative.split(','))
+            .map(str::trim)
+            .filter(|negative_id| !negative_id.is_empty())
+        {
+            let negative_id = negative_id.parse::<i64>().map_err(|_| {
+                SearchError::InvalidParams(format!(
+                    "negative must be comma separated HN ids, not `{}`",
+                    negative_id
+                ))
+            })?;
+            if negative_id != id && !negative.contains(&negative_id) {
+                negative.push(negative_id);
+            }
+        }
+        if negative.len() > config.max_negative {
+            return Err(SearchError::InvalidParams(format!(
+                "negative can hold at most {} items, not {}",
+                config.max_negative,
+                negative.len()
+            )));
+        }
+
+        Ok(SimilarRequest {
+            id,
+            q: params.q.clone().unwrap_or_default(),
+            post_type: parse_param(&params.post_type, PostType::default())
+                .map_err(SearchError::InvalidParams)?,
+            negative,
+            limit: config.default_page_size,
+        })
+    }
+}
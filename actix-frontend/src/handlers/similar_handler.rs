@@ -0,0 +1,191 @@
+use super::{
+    page_handler::{cacheable_html, render_failed},
+    search_handler::{get_recommendations, ScoreChunkMetadata, SimplifiedSearchResponse},
+    search_request::SimilarRequest,
+};
+use crate::{backend::SearchBackend, config::Config, errors::SearchError, Templates};
+use actix_web::{
+    get,
+    http::{
+        header::{CacheControl, CacheDirective},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use minijinja::context;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Deserialize, Serialize, ToSchema, Clone, Default, PartialEq)]
+pub struct SimilarQueryParams {
+    /// Inline filters, any other words are left out.
+    pub q: Option<String>,
+    pub post_type: Option<String>,
+    /// Comma separated ids of the items the results should be unlike.
+    pub negative: Option<String>,
+}
+
+impl SimilarQueryParams {
+    /// URL of the items similar to `id` for these params, leaving out the ones which aren't
+    /// set.
+    pub fn url(&self, id: i64) -> String {
+        let params = [
+            ("q", self.q.clone()),
+            ("post_type", self.post_type.clone()),
+            ("negative", self.negative.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| {
+            value
+                .filter(|value| !value.is_empty())
+                .map(|value| (name, value))
+        })
+        .collect::<Vec<(&str, String)>>();
+
+        match serde_urlencoded::to_string(&params) {
+            Ok(query_string) if !query_string.is_empty() => {
+                format!("/similar/{}?{}", id, query_string)
+            }
+            _ => format!("/similar/{}", id),
+        }
+    }
+}
+
+/// The item of the request, as a result so it renders like one, and the items similar to it.
+async fn get_similar(
+    backend: &dyn SearchBackend,
+    config: &Config,
+    request: &SimilarRequest,
+) -> Result<Option<(ScoreChunkMetadata, SimplifiedSearchResponse)>, SearchError> {
+    let Some(chunk) = backend.get_chunk(&request.id.to_string()).await? else {
+        return Ok(None);
+    };
+    let item = ScoreChunkMetadata {
+        chunk,
+        highlights: None,
+        score: 0.0,
+        snippets: vec![],
+        parent: None,
+    };
+    Ok(Some((
+        item,
+        get_recommendations(backend, config, request).await?,
+    )))
+}
+
+/// Items similar to another
+///
+/// Recommendations from Trieve for the story or comment `id`, unlike the items in `negative`.
+/// `post_type` and the inline filters of `q` narrow them down as they do a search.
+#[utoipa::path(
+    get,
+    path = "/similar/{id}",
+    tag = "search",
+    responses(
+        (status = 200, description = "HTML page with the similar items", body = String),
+        (status = 304, description = "The page is unchanged since the ETag in `If-None-Match`"),
+        (status = 400, description = "HTML page explaining which query param is invalid", body = String),
+        (status = 404, description = "HTML page explaining that there is no such item", body = String),
+        (status = "5XX", description = "HTML page explaining that the recommendations failed", body = String),
+    ),
+    params(
+        ("id" = i64, Path, description = "HN id of the story or comment"),
+        ("q" = Option<String>, Query, description = "Inline filters, such as `by:pg points>100`"),
+        ("post_type" = Option<String>, Query, description = "`all`, `story`, `comment`, `show`, `job` or `poll`"),
+        ("negative" = Option<String>, Query, description = "Comma separated HN ids of items the results should be unlike")
+    )
+)]
+#[get("/similar/{id}")]
+pub async fn similar(
+    req: HttpRequest,
+    templates: Templates<'_>,
+    config: web::Data<Config>,
+    backend: web::Data<dyn SearchBackend>,
+    id: web::Path<i64>,
+    query_params: web::Query<SimilarQueryParams>,
+) -> impl actix_web::Responder {
+    let id = id.into_inner();
+    let query_params = query_params.into_inner();
+    let templ = templates.get_template("similar.html").unwrap();
+    let similar = match SimilarRequest::new(id, &query_params, &config.search) {
+        Ok(request) => get_similar(backend.get_ref(), &config, &request)
+            .await
+            .map(|similar| similar.map(|similar| (request, similar))),
+        Err(err) => Err(err),
+    };
+    let (request, item, resp, status, error) = match similar {
+        Ok(Some((request, (item, resp)))) => {
+            (Some(request), Some(item), resp, StatusCode::OK, None)
+        }
+        Ok(None) => (
+            None,
+            None,
+            SimplifiedSearchResponse::default(),
+            StatusCode::NOT_FOUND,
+            Some(format!("Item {} isn't in the index.", id)),
+        ),
+        Err(err) => {
+            tracing::error!(error = %err, kind = err.kind(), "Loading items similar to {} failed", id);
+            (
+                None,
+                None,
+                SimplifiedSearchResponse::default(),
+                err.status_code(),
+                Some(err.user_message()),
+            )
+        }
+    };
+
+    // Each result links to this page with it added to the negative examples, unless it has
+    // no id or there are as many as allowed already.
+    let num_negative = request.as_ref().map_or(0, |request| request.negative.len());
+    let less_like_urls = resp
+        .chunks
+        .iter()
+        .map(|result| {
+            let result_id = result.chunk.tracking_id.clone().flatten()?;
+            if num_negative >= config.search.max_negative {
+                return None;
+            }
+            let negative = match &query_params.negative {
+                Some(negative) if !negative.trim().is_empty() => {
+                    format!("{},{}", negative, result_id)
+                }
+                _ => result_id,
+            };
+            Some(
+                SimilarQueryParams {
+                    negative: Some(negative),
+                    ..query_params.clone()
+                }
+                .url(id),
+            )
+        })
+        .collect::<Vec<Option<String>>>();
+
+    let response_body = match templ.render(context! {
+        id => id,
+        item => item,
+        results => resp.chunks,
+        less_like_urls => less_like_urls,
+        diagnostics => resp.diagnostics,
+        error => error,
+        filter => query_params.clone(),
+        num_negative => num_negative,
+        reset_url => SimilarQueryParams {
+            negative: None,
+            ..query_params.clone()
+        }
+        .url(id),
+    }) {
+        Ok(body) => body,
+        Err(err) => return render_failed(err),
+    };
+
+    match status {
+        StatusCode::OK => cacheable_html(&req, response_body, config.cache.ttl_secs),
+        status => HttpResponse::build(status)
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .body(response_body),
+    }
+}
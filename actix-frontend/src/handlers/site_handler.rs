@@ -0,0 +1,83 @@
+use super::page_handler::{cacheable_html, render_failed};
+use crate::{
+    backend::SearchBackend,
+    config::Config,
+    errors::SearchError,
+    site::{get_site, is_site},
+    Templates,
+};
+use actix_web::{
+    get,
+    http::{
+        header::{CacheControl, CacheDirective},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use minijinja::context;
+
+/// Stories from a site
+///
+/// The stories submitted from a host, or from the repositories of a GitHub user with
+/// `github.com/OWNER`: its top stories by points, the number submitted each year, who submits
+/// them most, along with a search scoped to it with `site:`.
+#[utoipa::path(
+    get,
+    path = "/site/{site}",
+    tag = "search",
+    responses(
+        (status = 200, description = "HTML page about the site", body = String),
+        (status = 304, description = "The page is unchanged since the ETag in `If-None-Match`"),
+        (status = 400, description = "HTML page explaining that `site:` doesn't take this site", body = String),
+        (status = 404, description = "HTML page explaining that no stories from the site are in the index", body = String),
+        (status = "5XX", description = "HTML page explaining that the site could not be loaded", body = String),
+    ),
+    params(
+        ("site" = String, Path, description = "Host without `www.`, such as `example.org`, or `github.com/OWNER`")
+    )
+)]
+#[get("/site/{site:.+}")]
+pub async fn site(
+    req: HttpRequest,
+    templates: Templates<'_>,
+    config: web::Data<Config>,
+    backend: web::Data<dyn SearchBackend>,
+    site: web::Path<String>,
+) -> impl actix_web::Responder {
+    let templ = templates.get_template("site.html").unwrap();
+    let page = if is_site(&site) {
+        get_site(backend.get_ref(), &site).await
+    } else {
+        Err(SearchError::InvalidParams(format!(
+            "`{}` isn't a site that can be searched",
+            site
+        )))
+    };
+    let (page, status, error) = match page {
+        Ok(Some(page)) => (Some(page), StatusCode::OK, None),
+        Ok(None) => (
+            None,
+            StatusCode::NOT_FOUND,
+            Some(format!("No stories from {} are in the index.", site)),
+        ),
+        Err(err) => {
+            tracing::error!(error = %err, kind = err.kind(), "Loading site {} failed", site);
+            (None, err.status_code(), Some(err.user_message()))
+        }
+    };
+
+    let response_body = match templ.render(context! {
+        page => page,
+        error => error,
+    }) {
+        Ok(body) => body,
+        Err(err) => return render_failed(err),
+    };
+
+    match status {
+        StatusCode::OK => cacheable_html(&req, response_body, config.cache.ttl_secs),
+        status => HttpResponse::build(status)
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .body(response_body),
+    }
+}
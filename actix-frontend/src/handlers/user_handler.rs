@@ -0,0 +1,83 @@
+use super::page_handler::{cacheable_html, render_failed};
+use crate::{
+    backend::SearchBackend,
+    config::Config,
+    errors::SearchError,
+    profile::{get_profile, is_username},
+    Templates,
+};
+use actix_web::{
+    get,
+    http::{
+        header::{CacheControl, CacheDirective},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use minijinja::context;
+
+/// Hacker News user
+///
+/// The stories and comments of a user in the index: their top stories by points, newest
+/// comments, total points, when they were active and the sites they submit most, along with
+/// a search scoped to them with `by:`.
+#[utoipa::path(
+    get,
+    path = "/user/{name}",
+    tag = "search",
+    responses(
+        (status = 200, description = "HTML page about the user", body = String),
+        (status = 304, description = "The page is unchanged since the ETag in `If-None-Match`"),
+        (status = 400, description = "HTML page explaining that the name isn't an HN username", body = String),
+        (status = 404, description = "HTML page explaining that the user has no items in the index", body = String),
+        (status = "5XX", description = "HTML page explaining that the user could not be loaded", body = String),
+    ),
+    params(
+        ("name" = String, Path, description = "HN username, case sensitive")
+    )
+)]
+#[get("/user/{name}")]
+pub async fn user(
+    req: HttpRequest,
+    templates: Templates<'_>,
+    config: web::Data<Config>,
+    backend: web::Data<dyn SearchBackend>,
+    name: web::Path<String>,
+) -> impl actix_web::Responder {
+    let templ = templates.get_template("user.html").unwrap();
+    let profile = if is_username(&name) {
+        get_profile(backend.get_ref(), &name).await
+    } else {
+        Err(SearchError::InvalidParams(format!(
+            "`{}` isn't an HN username",
+            name
+        )))
+    };
+    let (profile, status, error) = match profile {
+        Ok(Some(profile)) => (Some(profile), StatusCode::OK, None),
+        Ok(None) => (
+            None,
+            StatusCode::NOT_FOUND,
+            Some(format!("No items by {} are in the index.", name)),
+        ),
+        Err(err) => {
+            tracing::error!(error = %err, kind = err.kind(), "Loading user {} failed", name);
+            (None, err.status_code(), Some(err.user_message()))
+        }
+    };
+
+    let response_body = match templ.render(context! {
+        profile => profile,
+        error => error,
+    }) {
+        Ok(body) => body,
+        Err(err) => return render_failed(err),
+    };
+
+    match status {
+        StatusCode::OK => cacheable_html(&req, response_body, config.cache.ttl_secs),
+        status => HttpResponse::build(status)
+            .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+            .body(response_body),
+    }
+}
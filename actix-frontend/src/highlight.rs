@@ -0,0 +1,238 @@
+//! Snippets of HN texts around the words of the query, shown in place of the whole text of
+//! comments and text posts.
+//!
+//! Snippets are HTML: the text is escaped and only the `<mark>` tags around matched words are
+//! added, so they can be rendered with `|safe`.
+
+/// Snippets shown per result at most.
+pub const MAX_SNIPPETS: usize = 3;
+
+/// Words and quoted phrases of the query, lowercased. Negated ones are left out as they can't
+/// be in the results.
+pub fn query_terms(query: &str) -> Vec<String> {
+    let mut terms = vec![];
+    let mut rest = query.to_lowercase();
+    while let Some(start) = rest.find('"') {
+        let Some(len) = rest[start + 1..].find('"') else {
+            break;
+        };
+        let phrase = rest[start + 1..start + 1 + len].trim().to_string();
+        let negated = rest[..start].ends_with('-');
+        if !phrase.is_empty() && !negated {
+            terms.push(phrase);
+        }
+        let prefix_end = if negated { start - 1 } else { start };
+        rest = format!("{} {}", &rest[..prefix_end], &rest[start + len + 2..]);
+    }
+    for word in rest
+        .split_whitespace()
+        .filter(|word| !word.starts_with('-'))
+    {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        if !word.is_empty() {
+            terms.push(word.to_string());
+        }
+    }
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+    terms.dedup();
+    terms
+}
+
+/// Text of an HN HTML fragment: tags are dropped, paragraphs and line breaks become spaces
+/// and entities are decoded.
+pub fn plain_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => {
+                let tag = rest[start + 1..start + end]
+                    .trim_start_matches('/')
+                    .to_lowercase();
+                if ["p", "br", "pre"]
+                    .iter()
+                    .any(|name| tag.split([' ', '/']).next() == Some(name))
+                {
+                    text.push(' ');
+                }
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                rest = "";
+            }
+        }
+    }
+    text.push_str(rest);
+    decode_entities(&text)
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 8)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => match entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity
+                    .strip_prefix('#')
+                    .and_then(|dec| dec.parse().ok())
+                    .and_then(char::from_u32),
+            },
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether `text` has `term` at byte `idx`, ignoring ASCII case, at the start of a word.
+fn term_at(text: &str, idx: usize, term: &str) -> bool {
+    text.as_bytes()
+        .get(idx..idx + term.len())
+        .is_some_and(|candidate| candidate.eq_ignore_ascii_case(term.as_bytes()))
+        && !text[..idx]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+}
+
+/// Escapes the plain `text` and wraps each occurrence of the `terms` in `<mark>`.
+pub fn mark_terms(text: &str, terms: &[String]) -> String {
+    let mut marked = String::with_capacity(text.len());
+    let mut unmarked_from = 0;
+    let mut idx = 0;
+    while idx < text.len() {
+        let term = terms
+            .iter()
+            .find(|term| !term.is_empty() && term_at(text, idx, term));
+        match term {
+            Some(term) => {
+                marked.push_str(&escape_html(&text[unmarked_from..idx]));
+                marked.push_str("<mark>");
+                marked.push_str(&escape_html(&text[idx..idx + term.len()]));
+                marked.push_str("</mark>");
+                idx += term.len();
+                unmarked_from = idx;
+            }
+            None => {
+                idx += text[idx..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+    marked.push_str(&escape_html(&text[unmarked_from..]));
+    marked
+}
+
+/// Snippets of `text`: the `highlights` Trieve sent, or else up to [`MAX_SNIPPETS`] windows
+/// of `window` words around the terms. Empty when none of the terms are in the text.
+pub fn snippets(highlights: &[String], text: &str, terms: &[String], window: usize) -> Vec<String> {
+    let highlights = highlights
+        .iter()
+        .map(|highlight| plain_text(highlight))
+        .filter(|highlight| !highlight.is_empty())
+        .take(MAX_SNIPPETS)
+        .collect::<Vec<String>>();
+    if !highlights.is_empty() {
+        return highlights
+            .iter()
+            .map(|highlight| mark_terms(highlight, terms))
+            .collect();
+    }
+
+    let text = plain_text(text);
+    let words = text.split(' ').collect::<Vec<&str>>();
+    let mut snippets = vec![];
+    let mut next_idx = 0;
+    for (idx, word) in words.iter().enumerate() {
+        if snippets.len() == MAX_SNIPPETS {
+            break;
+        }
+        let lowercase = word.to_lowercase();
+        let word = lowercase.trim_start_matches(|c: char| !c.is_alphanumeric());
+        let matches = terms.iter().any(|term| {
+            let first_word = term.split(' ').next().unwrap_or_default();
+            !first_word.is_empty() && word.starts_with(first_word)
+        });
+        if idx < next_idx || !matches {
+            continue;
+        }
+        let start = idx.saturating_sub(window);
+        let end = (idx + window + 1).min(words.len());
+        let mut snippet = words[start..end].join(" ");
+        if start > 0 {
+            snippet = format!("…{}", snippet);
+        }
+        if end < words.len() {
+            snippet.push('…');
+        }
+        snippets.push(mark_terms(&snippet, terms));
+        next_idx = end;
+    }
+    snippets
+}
+
+/// Start of the text of an HN HTML fragment, cut at a word after at most `max_chars`.
+pub fn excerpt(html: &str, max_chars: usize) -> String {
+    let text = plain_text(html);
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let mut excerpt = String::new();
+    for word in text.split(' ') {
+        if excerpt.chars().count() + word.chars().count() + 1 > max_chars {
+            break;
+        }
+        if !excerpt.is_empty() {
+            excerpt.push(' ');
+        }
+        excerpt.push_str(word);
+    }
+    if excerpt.is_empty() {
+        excerpt = text.chars().take(max_chars).collect();
+    }
+    excerpt.push('…');
+    excerpt
+}
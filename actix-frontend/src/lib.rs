@@ -1,4 +1,13 @@
-use crate::handlers::page_handler;
+use crate::{
+    backend::{
+        cache::{CacheStats, CacheStore, MemoryCache},
+        CachedBackend, InMemoryBackend, SearchBackend, TrieveBackend,
+    },
+    config::{BackendKind, Config},
+    handlers::{
+        api_handler, item_handler, page_handler, similar_handler, site_handler, user_handler,
+    },
+};
 use actix_cors::Cors;
 use actix_files::Files;
 use actix_web::{
@@ -8,7 +17,7 @@ use actix_web::{
     App, HttpServer,
 };
 use minijinja::Environment;
-use reqwest::ClientBuilder;
+use std::{sync::Arc, time::Duration};
 use tracing_actix_web::TracingLogger;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 use utoipa::OpenApi;
@@ -16,8 +25,16 @@ use utoipa_redoc::{Redoc, Servable};
 
 type Templates<'a> = Data<Environment<'a>>;
 
+pub mod backend;
+pub mod config;
+pub mod errors;
 pub mod formatting;
 pub mod handlers;
+pub mod highlight;
+pub mod profile;
+pub mod query;
+pub mod site;
+pub mod thread;
 
 #[derive(OpenApi)]
 #[openapi(
@@ -43,27 +60,102 @@ pub mod handlers;
     ),
     paths(
         handlers::page_handler::homepage,
+        handlers::item_handler::item,
+        handlers::user_handler::user,
+        handlers::site_handler::site,
+        handlers::similar_handler::similar,
+        handlers::api_handler::search,
+        handlers::api_handler::cache_stats,
     ),
     components(
         schemas(),
     ),
     tags(
         (name = "search", description = "Endpoint for processing search queries."),
+        (name = "metrics", description = "Endpoints reporting how the server is doing."),
     ),
 )]
 pub struct ApiDoc;
 
+/// The embedded templates along with the filters and globals they use.
+pub fn templates(config: &Config) -> Environment<'static> {
+    let mut env = Environment::new();
+    env.add_filter("time_ago", formatting::time_ago);
+    env.add_filter("format_link", formatting::format_link);
+    env.add_filter("sanitize", formatting::sanitize_html);
+    env.add_filter("round_score", formatting::round_score);
+    env.add_global(
+        "default_search_type",
+        minijinja::Value::from_serialize(config.search.default_search_type),
+    );
+    minijinja_embed::load_templates!(&mut env);
+    env
+}
+
+/// Wraps `backend` in the configured cache, or returns it as is when the cache is off.
+async fn with_cache(
+    backend: Arc<dyn SearchBackend>,
+    config: &Config,
+    stats: Arc<CacheStats>,
+) -> Arc<dyn SearchBackend> {
+    let dataset = match config.search_backend {
+        BackendKind::Trieve => config.trieve.dataset_id.clone(),
+        BackendKind::Memory => format!("memory:{}", config.fixtures.display()),
+    };
+    let ttl = Duration::from_secs(config.cache.ttl_secs);
+    let store: Box<dyn CacheStore> = if !config.cache.redis_url.is_empty() {
+        #[cfg(feature = "redis")]
+        match backend::cache::RedisCache::connect(&config.cache.redis_url, ttl).await {
+            Ok(store) => Box::new(store),
+            Err(err) => {
+                eprintln!(
+                    "Invalid configuration: could not connect to REDIS_URL: {}",
+                    err
+                );
+                std::process::exit(1);
+            }
+        }
+        #[cfg(not(feature = "redis"))]
+        unreachable!("REDIS_URL is rejected by Config::validate without the redis feature")
+    } else if config.cache.capacity > 0 {
+        Box::new(MemoryCache::new(config.cache.capacity, ttl))
+    } else {
+        return backend;
+    };
+    Arc::new(CachedBackend::new(backend, store, stats, &dataset))
+}
+
 #[get("/openapi.json")]
 pub async fn get_openapi_spec_handler() -> impl actix_web::Responder {
     web::Json(ApiDoc::openapi())
 }
 
 pub fn main() -> std::io::Result<()> {
-    dotenvy::dotenv().ok();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Invalid configuration: {}", err);
+            std::process::exit(1);
+        }
+    };
 
-    let trieve_reqwest_client = ClientBuilder::new()
-        .build()
-        .expect("Failed to create reqwest client");
+    let backend: Arc<dyn SearchBackend> = match config.search_backend {
+        BackendKind::Trieve => Arc::new(
+            TrieveBackend::from_config(config.trieve.clone())
+                .expect("Failed to create reqwest client"),
+        ),
+        BackendKind::Memory => match InMemoryBackend::from_file(&config.fixtures) {
+            Ok(backend) => Arc::new(backend),
+            Err(err) => {
+                eprintln!(
+                    "Invalid configuration: could not load {}: {}",
+                    config.fixtures.display(),
+                    err
+                );
+                std::process::exit(1);
+            }
+        },
+    };
 
     tracing_subscriber::Registry::default()
         .with(
@@ -74,30 +166,34 @@ pub fn main() -> std::io::Result<()> {
         )
         .init();
 
+    let bind_address = config.bind_address.clone();
     actix_web::rt::System::new().block_on(async move {
+        let cache_stats = Arc::new(CacheStats::default());
+        let backend = with_cache(backend, &config, cache_stats.clone()).await;
         HttpServer::new(move || {
-            // Load templates
-            let mut env = Environment::new();
-            env.add_filter("time_ago", formatting::time_ago);
-            env.add_filter("format_link", formatting::format_link);
-            env.add_filter("round_score", formatting::round_score);
-            minijinja_embed::load_templates!(&mut env);
-
             App::new()
-                .app_data(web::Data::new(env))
+                .app_data(web::Data::new(templates(&config)))
                 .wrap(TracingLogger::default())
                 .wrap(Cors::permissive())
                 .wrap(Compress::default())
                 .wrap(Logger::new("%r %s %b %{Referer}i %{User-Agent}i %T"))
-                .app_data(web::Data::new(trieve_reqwest_client.clone()))
+                .app_data(web::Data::new(config.clone()))
+                .app_data(web::Data::from(backend.clone()))
+                .app_data(web::Data::from(cache_stats.clone()))
                 .service(Redoc::with_url("/redoc", ApiDoc::openapi()))
                 .service(get_openapi_spec_handler)
                 .service(page_handler::homepage)
+                .service(item_handler::item)
+                .service(user_handler::user)
+                .service(site_handler::site)
+                .service(similar_handler::similar)
+                .service(api_handler::search)
+                .service(api_handler::cache_stats)
                 .service(page_handler::about)
                 .service(page_handler::help)
-                .service(Files::new("/static", "./static"))
+                .service(Files::new("/static", &config.static_dir))
         })
-        .bind(("0.0.0.0", 9000))?
+        .bind(bind_address)?
         .run()
         .await
     })?;
@@ -0,0 +1,161 @@
+//! Pages about an HN user, built from sorted listings and counts of the items tagged with
+//! their name.
+
+use crate::{
+    backend::{count_chunks, scroll_all, sorted_chunks, SearchBackend},
+    errors::SearchError,
+    formatting::format_link,
+    handlers::search_handler::{
+        add_parent_comments, CustomChunkFilter, CustomConditionType, ScoreChunkMetadata,
+    },
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use trieve_client::models::{MatchCondition, SortOrder};
+
+/// Most stories of a user read to sum their points and sites.
+pub const MAX_PROFILE_STORIES: usize = 10_000;
+
+/// Stories and comments listed on a profile.
+const PROFILE_LIST_LEN: usize = 10;
+
+/// Sites listed on a profile.
+const PROFILE_TOP_SITES: usize = 5;
+
+/// A site and how many of the user's stories link to it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SiteCount {
+    pub site: String,
+    pub count: usize,
+}
+
+/// What the index knows about a user. Points only count stories, as HN doesn't publish the
+/// score of comments, and jobs and polls are left out like in a `post_type=story` search.
+#[derive(Debug, Clone, Serialize)]
+pub struct UserProfile {
+    pub name: String,
+    pub num_stories: usize,
+    pub num_comments: usize,
+    pub total_points: i64,
+    /// Time of their oldest and newest items.
+    pub first_active: i64,
+    pub last_active: i64,
+    pub top_sites: Vec<SiteCount>,
+    /// The user has more than [`MAX_PROFILE_STORIES`] stories, so `total_points` and
+    /// `top_sites` only count some of them.
+    pub truncated: bool,
+    /// Their stories with the most points.
+    pub top_stories: Vec<ScoreChunkMetadata>,
+    /// Their newest comments, with the comment they reply to.
+    pub recent_comments: Vec<ScoreChunkMetadata>,
+}
+
+/// Whether `name` could be an HN username, which are 2 to 15 letters, digits, `-` or `_`.
+pub fn is_username(name: &str) -> bool {
+    (2..=15).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Filter on the items by `name`, only the ones of `item_type` when set.
+fn authored_by(name: &str, item_type: Option<&str>) -> CustomChunkFilter {
+    let mut filter = CustomChunkFilter::tagged(&[name.to_string()]);
+    // Author tags can collide with type and site tags, such as a user named `show`.
+    let mut must = filter.must.take().flatten().unwrap_or_default();
+    must.push(CustomConditionType::field_is(
+        "metadata.by",
+        MatchCondition::String(name.to_string()),
+    ));
+    if let Some(item_type) = item_type {
+        must.push(CustomConditionType::field_is(
+            "metadata.type",
+            MatchCondition::String(item_type.to_string()),
+        ));
+    }
+    filter.must = Some(Some(must));
+    filter
+}
+
+/// Time of the item first or last by `direction`.
+async fn active_at(
+    backend: &dyn SearchBackend,
+    name: &str,
+    direction: SortOrder,
+) -> Result<Option<i64>, SearchError> {
+    let items = sorted_chunks(backend, authored_by(name, None), "time_stamp", direction, 1).await?;
+    Ok(items
+        .first()
+        .and_then(|item| item.chunk.metadata.clone().flatten()?["time"].as_i64()))
+}
+
+/// Profile of the user `name`, `None` when none of their items are in the index.
+pub async fn get_profile(
+    backend: &dyn SearchBackend,
+    name: &str,
+) -> Result<Option<UserProfile>, SearchError> {
+    let (Some(first_active), Some(last_active)) = (
+        active_at(backend, name, SortOrder::Asc).await?,
+        active_at(backend, name, SortOrder::Desc).await?,
+    ) else {
+        return Ok(None);
+    };
+    let num_stories = count_chunks(backend, authored_by(name, Some("story"))).await?;
+    let num_comments = count_chunks(backend, authored_by(name, Some("comment"))).await?;
+
+    let stories = scroll_all(
+        backend,
+        authored_by(name, Some("story")),
+        MAX_PROFILE_STORIES,
+    )
+    .await?;
+    let mut total_points = 0;
+    let mut sites: HashMap<String, usize> = HashMap::new();
+    for metadata in stories
+        .chunks
+        .into_iter()
+        .filter_map(|chunk| chunk.metadata.flatten())
+    {
+        total_points += metadata["score"].as_i64().unwrap_or_default();
+        if let Some(url) = metadata["url"].as_str().filter(|url| !url.is_empty()) {
+            *sites.entry(format_link(url)).or_default() += 1;
+        }
+    }
+    let mut top_sites = sites
+        .into_iter()
+        .map(|(site, count)| SiteCount { site, count })
+        .collect::<Vec<SiteCount>>();
+    top_sites.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.site.cmp(&b.site)));
+    top_sites.truncate(PROFILE_TOP_SITES);
+
+    let top_stories = sorted_chunks(
+        backend,
+        authored_by(name, Some("story")),
+        "num_value",
+        SortOrder::Desc,
+        PROFILE_LIST_LEN,
+    )
+    .await?;
+    let mut recent_comments = sorted_chunks(
+        backend,
+        authored_by(name, Some("comment")),
+        "time_stamp",
+        SortOrder::Desc,
+        PROFILE_LIST_LEN,
+    )
+    .await?;
+    add_parent_comments(backend, &mut recent_comments).await;
+
+    Ok(Some(UserProfile {
+        name: name.to_string(),
+        num_stories,
+        num_comments,
+        total_points,
+        first_active,
+        last_active,
+        top_sites,
+        truncated: stories.truncated,
+        top_stories,
+        recent_comments,
+    }))
+}
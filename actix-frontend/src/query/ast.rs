@@ -0,0 +1,167 @@
+use super::diagnostics::Diagnostic;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+/// Byte offsets into the original query string, end exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start.min(other.start), self.end.max(other.end))
+    }
+
+    pub fn slice(self, source: &str) -> &str {
+        source.get(self.start..self.end).unwrap_or_default()
+    }
+}
+
+/// A parsed search query. Top level clauses are implicitly AND'd together.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub source: String,
+    pub clauses: Vec<Clause>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clause {
+    pub negated: bool,
+    pub node: Node,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// Bare word sent to Trieve as part of the text query.
+    Term(String),
+    /// Quoted sequence of words which must appear together.
+    Phrase(String),
+    /// Inline operator such as `by:pg` or `points>100`.
+    Filter(Filter),
+    /// Parenthesized or `OR`'d clauses. Each alternative is a list of AND'd clauses and
+    /// the group matches when any alternative does.
+    Group(Vec<Vec<Clause>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Author(String),
+    Site(String),
+    /// Any of the comma separated post types, e.g. `type:show,ask`.
+    Type(Vec<String>),
+    /// Comments under the story with this ID, written `thread:ID` or `story:ID`.
+    Thread(i64),
+    /// Substring of a metadata field, e.g. `title:"rust async"`.
+    Contains(TextField, String),
+    /// The metadata field is not empty, e.g. `has:url`.
+    Has(TextField),
+    Is(Predicate),
+    /// Every comparison has to hold, e.g. `points:100..500` is `>= 100` and `<= 500`.
+    Points(Vec<Comparison<i64>>),
+    Comments(Vec<Comparison<i64>>),
+    Date(Vec<Comparison<DateValue>>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    Title,
+    Text,
+    Url,
+}
+
+impl TextField {
+    pub fn metadata_field(self) -> &'static str {
+        match self {
+            TextField::Title => "metadata.title",
+            TextField::Text => "metadata.text",
+            TextField::Url => "metadata.url",
+        }
+    }
+
+    /// Tag the ingest gives items where this field is not empty.
+    pub fn has_tag(self) -> &'static str {
+        match self {
+            TextField::Title => "has:title",
+            TextField::Text => "has:text",
+            TextField::Url => "has:url",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Predicate {
+    /// Comment replying directly to the story, i.e. its `parent` is its `top_parent_id`.
+    TopLevel,
+}
+
+impl Predicate {
+    /// Tag the ingest gives items the predicate holds for.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Predicate::TopLevel => "is:top-level",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    /// Written `:` or `=`. For dates this means within the given day, month or year.
+    Eq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateValue {
+    Day(NaiveDate),
+    Month {
+        year: i32,
+        month: u32,
+    },
+    Year(i32),
+    /// Exact point in time such as `2024-01-01T12:00:00Z`, as a unix timestamp.
+    Instant(i64),
+    /// Relative to the time of the search, e.g. `7d` is seven days ago.
+    Ago(RelativeDate),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelativeDate {
+    pub amount: i64,
+    pub unit: DateUnit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateUnit {
+    Hours,
+    Days,
+    Weeks,
+    Years,
+}
+
+impl DateUnit {
+    pub fn seconds(self) -> i64 {
+        match self {
+            DateUnit::Hours => 60 * 60,
+            DateUnit::Days => 24 * 60 * 60,
+            DateUnit::Weeks => 7 * 24 * 60 * 60,
+            DateUnit::Years => 365 * 24 * 60 * 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison<T> {
+    pub op: CompareOp,
+    pub value: T,
+}
@@ -0,0 +1,254 @@
+use super::ast::{
+    Clause, CompareOp, Comparison, DateUnit, DateValue, Filter, Node, Predicate, Span, TextField,
+};
+use chrono::{DateTime, SecondsFormat};
+use std::fmt::Write;
+
+/// Rewrites clauses into their canonical order so equivalent queries compare equal:
+/// terms and phrases keep their relative order, followed by filters sorted by operator
+/// and then by groups. Duplicate filters are dropped, `type:` lists are sorted and a
+/// positive group with a single alternative is flattened into its parent. Spans no
+/// longer point into any source and are reset.
+pub fn canonicalize(clauses: &[Clause]) -> Vec<Clause> {
+    let mut text = vec![];
+    let mut filters = vec![];
+    let mut groups = vec![];
+
+    for clause in clauses {
+        match &clause.node {
+            Node::Term(_) | Node::Phrase(_) => text.push(Clause {
+                span: Span::default(),
+                ..clause.clone()
+            }),
+            Node::Filter(filter) => filters.push(Clause {
+                negated: clause.negated,
+                node: Node::Filter(canonical_filter(filter)),
+                span: Span::default(),
+            }),
+            Node::Group(alternatives) => {
+                let mut alternatives = alternatives
+                    .iter()
+                    .map(|clauses| canonicalize(clauses))
+                    .filter(|clauses| !clauses.is_empty())
+                    .collect::<Vec<Vec<Clause>>>();
+                match alternatives.len() {
+                    0 => {}
+                    1 if !clause.negated => {
+                        let flattened = alternatives.pop().unwrap_or_default();
+                        for clause in flattened {
+                            match clause.node {
+                                Node::Term(_) | Node::Phrase(_) => text.push(clause),
+                                Node::Filter(_) => filters.push(clause),
+                                Node::Group(_) => groups.push(clause),
+                            }
+                        }
+                    }
+                    _ => groups.push(Clause {
+                        negated: clause.negated,
+                        node: Node::Group(alternatives),
+                        span: Span::default(),
+                    }),
+                }
+            }
+        }
+    }
+
+    filters.sort_by_cached_key(|clause| {
+        let Node::Filter(filter) = &clause.node else {
+            unreachable!("only filters are sorted")
+        };
+        (
+            filter_rank(filter),
+            clause.negated,
+            serialize_clause(clause),
+        )
+    });
+    filters.dedup();
+
+    text.into_iter().chain(filters).chain(groups).collect()
+}
+
+/// Writes clauses back out as a query string which parses to the same clauses. A query
+/// consisting of a single `OR` group is written without the surrounding parentheses.
+pub fn serialize(clauses: &[Clause]) -> String {
+    match clauses {
+        [Clause {
+            negated: false,
+            node: Node::Group(alternatives),
+            ..
+        }] => serialize_alternatives(alternatives),
+        _ => serialize_and(clauses),
+    }
+}
+
+fn serialize_and(clauses: &[Clause]) -> String {
+    clauses
+        .iter()
+        .map(serialize_clause)
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn serialize_alternatives(alternatives: &[Vec<Clause>]) -> String {
+    alternatives
+        .iter()
+        .map(|clauses| serialize_and(clauses))
+        .collect::<Vec<String>>()
+        .join(" OR ")
+}
+
+fn serialize_clause(clause: &Clause) -> String {
+    let mut out = String::new();
+    match &clause.node {
+        // Single character tags only parse in the legacy `by:-x` spelling, which is
+        // negated once more to include them.
+        Node::Filter(Filter::Author(tag) | Filter::Site(tag)) if tag.chars().count() < 2 => {
+            let name = match clause.node {
+                Node::Filter(Filter::Author(_)) => "by",
+                _ => "site",
+            };
+            let prefix = if clause.negated { "" } else { "-" };
+            let _ = write!(out, "{}{}:-{}", prefix, name, tag);
+            return out;
+        }
+        _ if clause.negated => out.push('-'),
+        _ => {}
+    }
+    match &clause.node {
+        Node::Term(term) => out.push_str(term),
+        Node::Phrase(phrase) => {
+            let _ = write!(out, "\"{}\"", phrase);
+        }
+        Node::Filter(filter) => write_filter(&mut out, filter),
+        Node::Group(alternatives) => {
+            let _ = write!(out, "({})", serialize_alternatives(alternatives));
+        }
+    }
+    out
+}
+
+fn canonical_filter(filter: &Filter) -> Filter {
+    match filter {
+        Filter::Type(post_types) => {
+            let mut post_types = post_types.clone();
+            post_types.sort();
+            post_types.dedup();
+            Filter::Type(post_types)
+        }
+        filter => filter.clone(),
+    }
+}
+
+fn filter_rank(filter: &Filter) -> u8 {
+    match filter {
+        Filter::Author(_) => 0,
+        Filter::Site(_) => 1,
+        Filter::Type(_) => 2,
+        Filter::Thread(_) => 3,
+        Filter::Contains(TextField::Title, _) => 4,
+        Filter::Contains(TextField::Text, _) => 5,
+        Filter::Contains(TextField::Url, _) => 6,
+        Filter::Has(_) => 7,
+        Filter::Is(_) => 8,
+        Filter::Points(_) => 9,
+        Filter::Comments(_) => 10,
+        Filter::Date(_) => 11,
+    }
+}
+
+fn write_filter(out: &mut String, filter: &Filter) {
+    let _ = match filter {
+        Filter::Author(author) => write!(out, "by:{}", author),
+        Filter::Site(site) => write!(out, "site:{}", site),
+        Filter::Type(post_types) => write!(out, "type:{}", post_types.join(",")),
+        Filter::Thread(id) => write!(out, "thread:{}", id),
+        Filter::Contains(field, text) => write!(out, "{}:{}", field_name(*field), text_value(text)),
+        Filter::Has(field) => write!(out, "has:{}", field_name(*field)),
+        Filter::Is(Predicate::TopLevel) => write!(out, "is:top-level"),
+        Filter::Points(comparisons) => {
+            write_comparisons(out, "points", comparisons, |id| id.to_string());
+            Ok(())
+        }
+        Filter::Comments(comparisons) => {
+            write_comparisons(out, "comments", comparisons, |id| id.to_string());
+            Ok(())
+        }
+        Filter::Date(comparisons) => {
+            write_comparisons(out, "date", comparisons, date_value);
+            Ok(())
+        }
+    };
+}
+
+/// Quotes values the lexer would otherwise cut short: at whitespace, at a `)` inside a
+/// group or at a `"`, which is escaped along with backslashes.
+fn text_value(text: &str) -> String {
+    if !text.contains(|c: char| c.is_whitespace() || c == ')' || c == '"') {
+        return text.to_string();
+    }
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn field_name(field: TextField) -> &'static str {
+    match field {
+        TextField::Title => "title",
+        TextField::Text => "text",
+        TextField::Url => "url",
+    }
+}
+
+/// Writes comparisons the way the parser reads them: a `>=` and `<=` pair becomes an
+/// interval such as `points:100..500`.
+fn write_comparisons<T>(
+    out: &mut String,
+    name: &str,
+    comparisons: &[Comparison<T>],
+    format: impl Fn(&T) -> String,
+) {
+    if let [Comparison {
+        op: CompareOp::Gte,
+        value: lower,
+    }, Comparison {
+        op: CompareOp::Lte,
+        value: upper,
+    }] = comparisons
+    {
+        let _ = write!(out, "{}:{}..{}", name, format(lower), format(upper));
+        return;
+    }
+
+    let comparisons = comparisons
+        .iter()
+        .map(|comparison| {
+            let op = match comparison.op {
+                CompareOp::Gt => ">",
+                CompareOp::Gte => ">=",
+                CompareOp::Lt => "<",
+                CompareOp::Lte => "<=",
+                CompareOp::Eq => ":",
+            };
+            format!("{}{}{}", name, op, format(&comparison.value))
+        })
+        .collect::<Vec<String>>();
+    out.push_str(&comparisons.join(" "));
+}
+
+fn date_value(value: &DateValue) -> String {
+    match value {
+        DateValue::Day(day) => day.format("%Y-%m-%d").to_string(),
+        DateValue::Month { year, month } => format!("{:04}-{:02}", year, month),
+        DateValue::Year(year) => format!("{:04}", year),
+        DateValue::Instant(timestamp) => DateTime::from_timestamp(*timestamp, 0)
+            .unwrap_or_default()
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        DateValue::Ago(relative) => {
+            let unit = match relative.unit {
+                DateUnit::Hours => 'h',
+                DateUnit::Days => 'd',
+                DateUnit::Weeks => 'w',
+                DateUnit::Years => 'y',
+            };
+            format!("{}{}", relative.amount, unit)
+        }
+    }
+}
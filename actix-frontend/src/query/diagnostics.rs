@@ -0,0 +1,39 @@
+use super::ast::Span;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// Looks like an operator but isn't one, kept in the text query.
+    UnknownOperator,
+    /// Known operator with a value that couldn't be parsed, dropped from the query.
+    InvalidValue,
+    InvalidDate,
+    /// Filters on the same field which no result can satisfy at once.
+    ConflictingRange,
+    /// Nothing but filters, so Trieve is asked to rank against an empty query.
+    EmptyQuery,
+    UnbalancedParenthesis,
+    DanglingOr,
+}
+
+/// A warning about part of the query that was not applied the way it was written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub message: String,
+    pub span: Span,
+    /// The offending part of the query, as typed.
+    pub fragment: String,
+}
+
+impl Diagnostic {
+    pub fn new(kind: DiagnosticKind, span: Span, source: &str, message: String) -> Self {
+        Diagnostic {
+            kind,
+            message,
+            span,
+            fragment: span.slice(source).to_string(),
+        }
+    }
+}
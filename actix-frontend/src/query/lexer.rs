@@ -0,0 +1,138 @@
+use super::ast::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// Run of non-whitespace characters. A quote directly after a `:` (e.g.
+    /// `title:"rust async"`) is kept verbatim and may span whitespace and parentheses.
+    Word(String),
+    /// Quoted phrase with the surrounding quotes removed.
+    Phrase(String),
+    /// Leading `-` directly attached to the following token.
+    Minus,
+    /// Upper case `OR` keyword between two clauses or groups.
+    Or,
+    LParen,
+    /// Closing parenthesis. Only produced while a group is open, so a stray `)` stays
+    /// part of the word it is attached to.
+    RParen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+pub fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+    let mut depth = 0;
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '(' {
+            chars.next();
+            depth += 1;
+            tokens.push(Token {
+                kind: TokenKind::LParen,
+                span: Span::new(start, start + 1),
+            });
+            continue;
+        }
+
+        if c == ')' && depth > 0 {
+            chars.next();
+            depth -= 1;
+            tokens.push(Token {
+                kind: TokenKind::RParen,
+                span: Span::new(start, start + 1),
+            });
+            continue;
+        }
+
+        if c == '-' {
+            chars.next();
+            match chars.peek() {
+                Some(&(_, next))
+                    if !next.is_whitespace() && next != '-' && !(next == ')' && depth > 0) =>
+                {
+                    tokens.push(Token {
+                        kind: TokenKind::Minus,
+                        span: Span::new(start, start + 1),
+                    });
+                    continue;
+                }
+                _ => {
+                    let end = read_word(&mut chars, start + 1, depth > 0);
+                    tokens.push(Token {
+                        kind: TokenKind::Word(input[start..end].to_string()),
+                        span: Span::new(start, end),
+                    });
+                    continue;
+                }
+            }
+        }
+
+        if c == '"' {
+            chars.next();
+            let mut end = input.len();
+            let mut content_end = input.len();
+            for (idx, c) in chars.by_ref() {
+                if c == '"' {
+                    content_end = idx;
+                    end = idx + 1;
+                    break;
+                }
+            }
+            tokens.push(Token {
+                kind: TokenKind::Phrase(input[start + 1..content_end].to_string()),
+                span: Span::new(start, end),
+            });
+            continue;
+        }
+
+        chars.next();
+        let end = read_word(&mut chars, start + c.len_utf8(), depth > 0);
+        let word = &input[start..end];
+        tokens.push(Token {
+            kind: if word == "OR" {
+                TokenKind::Or
+            } else {
+                TokenKind::Word(word.to_string())
+            },
+            span: Span::new(start, end),
+        });
+    }
+
+    tokens
+}
+
+/// Consumes the rest of a word and returns its end offset. Inside a group the word
+/// also ends at a closing parenthesis. Within a quoted value, `\` escapes the next
+/// character so `\"` doesn't end the quotes.
+fn read_word(
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+    mut end: usize,
+    in_group: bool,
+) -> usize {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut prev = None;
+    while let Some(&(idx, c)) = chars.peek() {
+        if (c.is_whitespace() || (c == ')' && in_group)) && !in_quotes {
+            break;
+        }
+        if c == '"' && !escaped && (in_quotes || prev == Some(':')) {
+            in_quotes = !in_quotes;
+        }
+        escaped = in_quotes && c == '\\' && !escaped;
+        chars.next();
+        prev = Some(c);
+        end = idx + c.len_utf8();
+    }
+    end
+}
@@ -0,0 +1,572 @@
+use super::{
+    ast::{Clause, CompareOp, Comparison, DateValue, Filter, Node, Query, Span, TextField},
+    diagnostics::{Diagnostic, DiagnosticKind},
+};
+use crate::handlers::search_handler::{
+    CleanedQueriesAndSearchFilters, CustomChunkFilter, CustomConditionType,
+};
+use chrono::{NaiveDate, Utc};
+use trieve_client::models::{self, FieldCondition, MatchCondition};
+
+#[derive(Default)]
+struct TagFilter {
+    any: Vec<String>,
+    none: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+struct Bound {
+    value: i64,
+    inclusive: bool,
+}
+
+/// Bounds accumulated from every comparison on a field, keeping the tightest.
+#[derive(Default)]
+struct RangeFilter {
+    lower: Option<Bound>,
+    upper: Option<Bound>,
+    spans: Vec<Span>,
+}
+
+impl RangeFilter {
+    fn apply(&mut self, comparisons: &[Comparison<i64>], span: Span) {
+        self.spans.push(span);
+        for comparison in comparisons {
+            let (value, inclusive) = (comparison.value, comparison.op != CompareOp::Gt);
+            match comparison.op {
+                CompareOp::Gt | CompareOp::Gte => self.bound_below(Bound { value, inclusive }),
+                CompareOp::Lt | CompareOp::Lte => self.bound_above(Bound {
+                    value,
+                    inclusive: comparison.op == CompareOp::Lte,
+                }),
+                CompareOp::Eq => {
+                    self.bound_below(Bound { value, inclusive });
+                    self.bound_above(Bound { value, inclusive });
+                }
+            }
+        }
+    }
+
+    fn bound_below(&mut self, bound: Bound) {
+        if self.min().is_none_or(|min| min < min_value(bound)) {
+            self.lower = Some(bound);
+        }
+    }
+
+    fn bound_above(&mut self, bound: Bound) {
+        if self.max().is_none_or(|max| max > max_value(bound)) {
+            self.upper = Some(bound);
+        }
+    }
+
+    /// Smallest integer allowed by the lower bound.
+    fn min(&self) -> Option<i64> {
+        self.lower.map(min_value)
+    }
+
+    /// Largest integer allowed by the upper bound.
+    fn max(&self) -> Option<i64> {
+        self.upper.map(max_value)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lower.is_none() && self.upper.is_none()
+    }
+
+    fn is_unsatisfiable(&self) -> bool {
+        matches!((self.min(), self.max()), (Some(min), Some(max)) if min > max)
+    }
+
+    fn to_range(&self) -> models::Range {
+        let condition = |bound: Bound| Some(Box::new(models::RangeCondition::Integer(bound.value)));
+        let (mut range, lower, upper) = (models::Range::new(), self.lower, self.upper);
+        match lower {
+            Some(bound) if bound.inclusive => range.gte = Some(condition(bound)),
+            Some(bound) => range.gt = Some(condition(bound)),
+            None => {}
+        }
+        match upper {
+            Some(bound) if bound.inclusive => range.lte = Some(condition(bound)),
+            Some(bound) => range.lt = Some(condition(bound)),
+            None => {}
+        }
+        range
+    }
+}
+
+fn min_value(bound: Bound) -> i64 {
+    if bound.inclusive {
+        bound.value
+    } else {
+        bound.value.saturating_add(1)
+    }
+}
+
+fn max_value(bound: Bound) -> i64 {
+    if bound.inclusive {
+        bound.value
+    } else {
+        bound.value.saturating_sub(1)
+    }
+}
+
+/// Lowers a parsed query into the text sent to Trieve plus the must/must_not/should filters.
+///
+/// Terms inside groups are added to the text query as-is since only filters can be
+/// OR'd. A single top level group becomes `should`, any other group becomes a nested
+/// filter unless all of its alternatives are tags that fit in one `match_any`.
+pub fn lower(query: &Query) -> CleanedQueriesAndSearchFilters {
+    lower_at(query, Utc::now().timestamp())
+}
+
+/// Same as [`lower`], with relative dates such as `date>7d` counted back from the unix
+/// timestamp `now`.
+pub fn lower_at(query: &Query, now: i64) -> CleanedQueriesAndSearchFilters {
+    let mut lowering = Lowering {
+        now,
+        source: &query.source,
+        text_parts: vec![],
+        diagnostics: query.diagnostics.clone(),
+    };
+    let filter = lowering.lower_clauses(&query.clauses, false, true);
+
+    if lowering.text_parts.is_empty() && !query.clauses.is_empty() {
+        lowering.diagnostics.push(Diagnostic::new(
+            DiagnosticKind::EmptyQuery,
+            Span::new(0, query.source.len()),
+            &query.source,
+            "Only filters were given, so results are not ranked by any search terms.".to_string(),
+        ));
+    }
+
+    CleanedQueriesAndSearchFilters {
+        cleaned_query: lowering.text_parts.join(" "),
+        must_filters: filter.must,
+        must_not_filters: filter.must_not,
+        should_filters: filter.should,
+        diagnostics: lowering.diagnostics,
+    }
+}
+
+struct Lowering<'a> {
+    now: i64,
+    source: &'a str,
+    text_parts: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Default)]
+struct LoweredFilter {
+    must: Vec<CustomConditionType>,
+    must_not: Vec<CustomConditionType>,
+    should: Vec<CustomConditionType>,
+}
+
+impl LoweredFilter {
+    /// Collapses the filter into one condition, or `None` if it matches everything.
+    fn into_condition(self) -> Option<CustomConditionType> {
+        match (
+            self.must.len(),
+            self.must_not.is_empty() && self.should.is_empty(),
+        ) {
+            (0, true) => None,
+            (1, true) => self.must.into_iter().next(),
+            _ => Some(CustomConditionType::ChunkFilter(Box::new(
+                CustomChunkFilter {
+                    jsonb_prefilter: None,
+                    must: non_empty(self.must),
+                    must_not: non_empty(self.must_not),
+                    should: non_empty(self.should),
+                },
+            ))),
+        }
+    }
+}
+
+fn non_empty(conditions: Vec<CustomConditionType>) -> Option<Option<Vec<CustomConditionType>>> {
+    (!conditions.is_empty()).then_some(Some(conditions))
+}
+
+impl Lowering<'_> {
+    fn lower_clauses(
+        &mut self,
+        clauses: &[Clause],
+        negate_text: bool,
+        top_level: bool,
+    ) -> LoweredFilter {
+        let mut authors = TagFilter::default();
+        let mut sites = TagFilter::default();
+        let mut types = TagFilter::default();
+        let mut type_sets = vec![];
+        let mut flags = TagFilter::default();
+        let mut titles = TagFilter::default();
+        let mut texts = TagFilter::default();
+        let mut urls = TagFilter::default();
+        let mut points = RangeFilter::default();
+        let mut comments = RangeFilter::default();
+        let mut dates = RangeFilter::default();
+        let mut threads = RangeFilter::default();
+        let mut groups = vec![];
+        let mut lowered = LoweredFilter::default();
+
+        for clause in clauses {
+            let prefix = if clause.negated != negate_text {
+                "-"
+            } else {
+                ""
+            };
+            let filter = match &clause.node {
+                Node::Term(term) => {
+                    self.text_parts.push(format!("{}{}", prefix, term));
+                    continue;
+                }
+                Node::Phrase(phrase) => {
+                    self.text_parts.push(format!("{}\"{}\"", prefix, phrase));
+                    continue;
+                }
+                Node::Group(alternatives) => {
+                    groups.push((clause.negated, alternatives));
+                    continue;
+                }
+                Node::Filter(filter) => filter,
+            };
+
+            let tags = match filter {
+                Filter::Author(author) => Some((&mut authors, author.clone())),
+                Filter::Site(site) => {
+                    match clause.negated {
+                        true => sites.none.extend(site_tags(site)),
+                        false => sites.any.extend(site_tags(site)),
+                    }
+                    continue;
+                }
+                Filter::Type(post_types) if clause.negated || post_types.len() > 1 => {
+                    match clause.negated {
+                        true => types.none.extend(post_types.iter().cloned()),
+                        false => type_sets.push(post_types.clone()),
+                    }
+                    continue;
+                }
+                Filter::Type(post_types) => Some((&mut types, post_types.join(","))),
+                Filter::Has(field) => Some((&mut flags, field.has_tag().to_string())),
+                Filter::Is(predicate) => Some((&mut flags, predicate.tag().to_string())),
+                Filter::Contains(field, text) => {
+                    // Words scoped to the title or text still help rank the results.
+                    if !clause.negated && *field != TextField::Url {
+                        self.text_parts
+                            .push(match text.contains(char::is_whitespace) {
+                                true => format!("\"{}\"", text),
+                                false => text.clone(),
+                            });
+                    }
+                    match field {
+                        TextField::Title => Some((&mut titles, text.clone())),
+                        TextField::Text => Some((&mut texts, text.clone())),
+                        TextField::Url => Some((&mut urls, text.clone())),
+                    }
+                }
+                _ => None,
+            };
+            if let Some((tags, value)) = tags {
+                if clause.negated {
+                    tags.none.push(value);
+                } else {
+                    tags.any.push(value);
+                }
+                continue;
+            }
+
+            let (field, ranges, comparisons) = match filter {
+                Filter::Points(comparisons) => ("num_value", &mut points, comparisons.clone()),
+                Filter::Comments(comparisons) => {
+                    ("metadata.descendants", &mut comments, comparisons.clone())
+                }
+                Filter::Date(comparisons) => (
+                    "time_stamp",
+                    &mut dates,
+                    comparisons
+                        .iter()
+                        .flat_map(|comparison| date_bounds(comparison, self.now))
+                        .collect(),
+                ),
+                // Stories are stored with a top_parent_id of -1, so only their comments match.
+                Filter::Thread(id) => (
+                    "metadata.top_parent_id",
+                    &mut threads,
+                    vec![Comparison {
+                        op: CompareOp::Eq,
+                        value: *id,
+                    }],
+                ),
+                _ => continue,
+            };
+            if clause.negated {
+                let mut negated = RangeFilter::default();
+                negated.apply(&comparisons, clause.span);
+                lowered.must_not.push(range_condition(field, &negated));
+            } else {
+                ranges.apply(&comparisons, clause.span);
+            }
+        }
+
+        for tags in [&authors, &sites] {
+            if !tags.any.is_empty() {
+                lowered
+                    .must
+                    .push(match_condition("tag_set", &tags.any, false));
+            }
+            if !tags.none.is_empty() {
+                lowered
+                    .must_not
+                    .push(match_condition("tag_set", &tags.none, false));
+            }
+        }
+        for (field, texts) in [
+            (TextField::Title, &titles),
+            (TextField::Text, &texts),
+            (TextField::Url, &urls),
+        ] {
+            if !texts.any.is_empty() {
+                lowered
+                    .must
+                    .push(match_condition(field.metadata_field(), &texts.any, true));
+            }
+            if !texts.none.is_empty() {
+                lowered
+                    .must_not
+                    .push(match_condition(field.metadata_field(), &texts.none, false));
+            }
+        }
+        for (field, ranges) in [
+            ("num_value", &points),
+            ("metadata.descendants", &comments),
+            ("time_stamp", &dates),
+            ("metadata.top_parent_id", &threads),
+        ] {
+            if ranges.is_unsatisfiable() {
+                self.conflict(ranges);
+            }
+            if !ranges.is_empty() {
+                lowered.must.push(range_condition(field, ranges));
+            }
+        }
+        if !types.any.is_empty() {
+            lowered
+                .must
+                .push(match_condition("tag_set", &types.any, true));
+        }
+        for post_types in type_sets {
+            lowered
+                .must
+                .push(match_condition("tag_set", &post_types, false));
+        }
+        if !types.none.is_empty() {
+            lowered
+                .must_not
+                .push(match_condition("tag_set", &types.none, false));
+        }
+        if !flags.any.is_empty() {
+            lowered
+                .must
+                .push(match_condition("tag_set", &flags.any, true));
+        }
+        if !flags.none.is_empty() {
+            lowered
+                .must_not
+                .push(match_condition("tag_set", &flags.none, false));
+        }
+
+        for (negated, alternatives) in groups {
+            let conditions = alternatives
+                .iter()
+                .filter_map(|clauses| {
+                    self.lower_clauses(clauses, negate_text != negated, false)
+                        .into_condition()
+                })
+                .collect::<Vec<CustomConditionType>>();
+
+            if negated {
+                // -(a OR b) is -a -b
+                lowered.must_not.extend(conditions);
+            } else if conditions.len() < alternatives.len() {
+                // An alternative without filters matches everything, so the group does too.
+            } else if let Some(merged) = merge_tag_alternatives(&conditions) {
+                lowered.must.push(merged);
+            } else if top_level && lowered.should.is_empty() {
+                lowered.should = conditions;
+            } else {
+                lowered.must.push(CustomConditionType::ChunkFilter(Box::new(
+                    CustomChunkFilter {
+                        jsonb_prefilter: None,
+                        must: None,
+                        must_not: None,
+                        should: Some(Some(conditions)),
+                    },
+                )));
+            }
+        }
+
+        lowered
+    }
+
+    fn conflict(&mut self, ranges: &RangeFilter) {
+        let span = ranges
+            .spans
+            .iter()
+            .copied()
+            .reduce(Span::to)
+            .unwrap_or_default();
+        let fragment = ranges
+            .spans
+            .iter()
+            .map(|span| span.slice(self.source))
+            .collect::<Vec<&str>>()
+            .join(" ");
+        self.diagnostics.push(Diagnostic {
+            kind: DiagnosticKind::ConflictingRange,
+            message: "These filters can never match together, so nothing will be found."
+                .to_string(),
+            span,
+            fragment,
+        });
+    }
+}
+
+/// Tags the stories of `site` can have. Hosts are tagged as they appear in the URL while
+/// links show them without `www.`, so `site:example.org` also matches `www.example.org`.
+pub fn site_tags(site: &str) -> Vec<String> {
+    match site.starts_with("www.") || site.contains('/') {
+        true => vec![site.to_string()],
+        false => vec![site.to_string(), format!("www.{}", site)],
+    }
+}
+
+/// Alternatives which each match a single set of tags, e.g. `(by:pg OR site:paulgraham.com)`,
+/// are equivalent to one `match_any` over all of them.
+fn merge_tag_alternatives(conditions: &[CustomConditionType]) -> Option<CustomConditionType> {
+    let mut tags = vec![];
+    for condition in conditions {
+        let CustomConditionType::FieldCondition(condition) = condition else {
+            return None;
+        };
+        if condition.field != "tag_set" || condition.range.is_some() {
+            return None;
+        }
+        match (&condition.match_any, &condition.match_all) {
+            (Some(Some(any)), None) => tags.extend(any.iter().cloned()),
+            (None, Some(Some(all))) if all.len() == 1 => tags.extend(all.iter().cloned()),
+            _ => return None,
+        }
+    }
+
+    Some(CustomConditionType::FieldCondition(Box::new(
+        FieldCondition {
+            field: "tag_set".to_string(),
+            match_any: Some(Some(tags)),
+            date_range: None,
+            geo_bounding_box: None,
+            geo_polygon: None,
+            geo_radius: None,
+            match_all: None,
+            range: None,
+        },
+    )))
+}
+
+/// Turns a date comparison into bounds on `time_stamp`. Days, months and years cover
+/// their whole period, so `date>2024-01` starts in February and `date<=2024-01` ends
+/// with January.
+fn date_bounds(comparison: &Comparison<DateValue>, now: i64) -> Vec<Comparison<i64>> {
+    let (start, end) = match &comparison.value {
+        DateValue::Day(date) => (date_to_timestamp(*date), date_to_timestamp(*date) + DAY),
+        DateValue::Month { year, month } => {
+            let start = NaiveDate::from_ymd_opt(*year, *month, 1).unwrap_or_default();
+            let end = match month {
+                12 => NaiveDate::from_ymd_opt(year + 1, 1, 1),
+                _ => NaiveDate::from_ymd_opt(*year, month + 1, 1),
+            }
+            .unwrap_or_default();
+            (date_to_timestamp(start), date_to_timestamp(end))
+        }
+        DateValue::Year(year) => (
+            date_to_timestamp(NaiveDate::from_ymd_opt(*year, 1, 1).unwrap_or_default()),
+            date_to_timestamp(NaiveDate::from_ymd_opt(year + 1, 1, 1).unwrap_or_default()),
+        ),
+        DateValue::Instant(timestamp) => (*timestamp, timestamp + 1),
+        DateValue::Ago(relative) => {
+            let ago = now.saturating_sub(relative.amount.saturating_mul(relative.unit.seconds()));
+            // date:7d means within the last seven days
+            let op = match comparison.op {
+                CompareOp::Eq => CompareOp::Gte,
+                op => op,
+            };
+            return vec![Comparison { op, value: ago }];
+        }
+    };
+
+    let (op, value) = match comparison.op {
+        CompareOp::Gt => (CompareOp::Gte, end),
+        CompareOp::Gte => (CompareOp::Gte, start),
+        CompareOp::Lt => (CompareOp::Lt, start),
+        CompareOp::Lte => (CompareOp::Lt, end),
+        CompareOp::Eq => {
+            return vec![
+                Comparison {
+                    op: CompareOp::Gte,
+                    value: start,
+                },
+                Comparison {
+                    op: CompareOp::Lt,
+                    value: end,
+                },
+            ]
+        }
+    };
+    vec![Comparison { op, value }]
+}
+
+const DAY: i64 = 24 * 60 * 60;
+
+pub fn date_to_timestamp(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0)
+        .expect("0 offset always valid")
+        .and_local_timezone(Utc)
+        .unwrap()
+        .timestamp()
+}
+
+fn match_condition(field: &str, values: &[String], match_all: bool) -> CustomConditionType {
+    let values = values
+        .iter()
+        .map(|value| MatchCondition::String(value.clone()))
+        .collect::<Vec<MatchCondition>>();
+    let (match_all, match_any) = if match_all {
+        (Some(Some(values)), None)
+    } else {
+        (None, Some(Some(values)))
+    };
+
+    CustomConditionType::FieldCondition(Box::new(FieldCondition {
+        field: field.to_string(),
+        match_any,
+        date_range: None,
+        geo_bounding_box: None,
+        geo_polygon: None,
+        geo_radius: None,
+        match_all,
+        range: None,
+    }))
+}
+
+fn range_condition(field: &str, ranges: &RangeFilter) -> CustomConditionType {
+    CustomConditionType::FieldCondition(Box::new(FieldCondition {
+        field: field.to_string(),
+        match_any: None,
+        date_range: None,
+        geo_bounding_box: None,
+        geo_polygon: None,
+        geo_radius: None,
+        match_all: None,
+        range: Some(Some(Box::new(ranges.to_range()))),
+    }))
+}
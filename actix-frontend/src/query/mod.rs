@@ -0,0 +1,12 @@
+//! Inline search syntax: tokenizes the raw `q` string, parses it into a typed [`ast::Query`]
+//! and lowers that into the text query and filters sent to Trieve. [`canonical`] writes a
+//! parsed query back out in a normalized form for links and redirects.
+
+pub mod ast;
+pub mod canonical;
+pub mod diagnostics;
+pub mod lexer;
+pub mod lower;
+pub mod parser;
+
+pub use parser::parse;
@@ -0,0 +1,525 @@
+use super::{
+    ast::{
+        Clause, CompareOp, Comparison, DateUnit, DateValue, Filter, Node, Predicate, Query,
+        RelativeDate, Span, TextField,
+    },
+    diagnostics::{Diagnostic, DiagnosticKind},
+    lexer::{tokenize, Token, TokenKind},
+};
+use chrono::{DateTime, NaiveDate};
+
+enum Operator {
+    Filter {
+        filter: Filter,
+        negated: bool,
+    },
+    /// A known operator whose value could not be understood, e.g. `points>abc`.
+    Invalid(DiagnosticKind, String),
+    /// Looks like an operator but isn't one, searched for as a plain term.
+    Unknown(String),
+    /// Not an inline operator, searched for as a plain term.
+    Plain,
+}
+
+/// Parses a raw query string. The grammar is
+///
+/// ```text
+/// query   := and ("OR" and)*
+/// and     := unary*
+/// unary   := "-"? primary
+/// primary := "(" query ")" | phrase | word
+/// ```
+///
+/// Parsing never fails: unbalanced parentheses close at the end of input and a
+/// dangling `-` or `OR` is ignored. Anything not applied as written is reported in
+/// [`Query::diagnostics`].
+pub fn parse(input: &str) -> Query {
+    let mut parser = Parser {
+        source: input,
+        tokens: tokenize(input),
+        pos: 0,
+        diagnostics: vec![],
+    };
+    let mut alternatives = parser.parse_or(false);
+    let clauses = match alternatives.len() {
+        0 => vec![],
+        1 => alternatives.pop().unwrap_or_default(),
+        _ => vec![Clause {
+            negated: false,
+            span: group_span(&alternatives),
+            node: Node::Group(alternatives),
+        }],
+    };
+
+    Query {
+        source: input.to_string(),
+        clauses,
+        diagnostics: parser.diagnostics,
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Parser<'_> {
+    fn warn(&mut self, kind: DiagnosticKind, span: Span, message: String) {
+        self.diagnostics
+            .push(Diagnostic::new(kind, span, self.source, message));
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|token| &token.kind)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Parses `OR` separated alternatives, dropping empty ones.
+    fn parse_or(&mut self, in_group: bool) -> Vec<Vec<Clause>> {
+        let mut alternatives = vec![];
+        let mut last_or = None;
+        loop {
+            let start = self.pos;
+            let clauses = self.parse_and(in_group);
+            let next_or = match self.tokens.get(self.pos) {
+                Some(Token {
+                    kind: TokenKind::Or,
+                    span,
+                }) => Some(*span),
+                _ => None,
+            };
+
+            if !clauses.is_empty() {
+                alternatives.push(clauses);
+            } else if let (true, Some(span)) = (start == self.pos, next_or.or(last_or)) {
+                self.warn(
+                    DiagnosticKind::DanglingOr,
+                    span,
+                    "OR needs a clause on both sides, so it was ignored.".to_string(),
+                );
+            }
+
+            match next_or {
+                Some(span) => {
+                    self.next();
+                    last_or = Some(span);
+                }
+                None => break,
+            }
+        }
+        alternatives
+    }
+
+    fn parse_and(&mut self, in_group: bool) -> Vec<Clause> {
+        let mut clauses = vec![];
+        while let Some(kind) = self.peek() {
+            match kind {
+                TokenKind::Or => break,
+                TokenKind::RParen if in_group => break,
+                _ => {}
+            }
+
+            let Some(token) = self.next() else {
+                break;
+            };
+            let (negated, token) = match token.kind {
+                TokenKind::Minus => match self.peek() {
+                    Some(TokenKind::Or | TokenKind::RParen) | None => continue,
+                    Some(_) => (true, self.next().unwrap_or(token)),
+                },
+                _ => (false, token),
+            };
+            let start = if negated {
+                token.span.start - 1
+            } else {
+                token.span.start
+            };
+
+            match token.kind {
+                TokenKind::LParen => {
+                    let mut alternatives = self.parse_or(true);
+                    let end = match self.peek() {
+                        Some(TokenKind::RParen) => self.next().map(|token| token.span.end),
+                        _ => {
+                            self.warn(
+                                DiagnosticKind::UnbalancedParenthesis,
+                                token.span,
+                                "This parenthesis is never closed, so the group runs to the end of the query.".to_string(),
+                            );
+                            None
+                        }
+                    };
+                    let span = Span::new(start, end.unwrap_or(group_span(&alternatives).end));
+                    match alternatives.len() {
+                        0 => {}
+                        1 if !negated => clauses.append(&mut alternatives[0]),
+                        _ => clauses.push(Clause {
+                            negated,
+                            node: Node::Group(alternatives),
+                            span,
+                        }),
+                    }
+                }
+                _ => {
+                    if let Some(clause) = self.parse_clause(token, negated, start) {
+                        clauses.push(clause);
+                    }
+                }
+            }
+        }
+        clauses
+    }
+
+    fn parse_clause(&mut self, token: Token, negated: bool, start: usize) -> Option<Clause> {
+        let span = Span::new(start, token.span.end);
+        let (negated, node) = match token.kind {
+            TokenKind::Phrase(phrase) if phrase.trim().is_empty() => return None,
+            TokenKind::Phrase(phrase) => (negated, Node::Phrase(phrase)),
+            TokenKind::Word(word) => match parse_operator(&word) {
+                Operator::Filter {
+                    filter,
+                    negated: value_negated,
+                } => (negated != value_negated, Node::Filter(filter)),
+                Operator::Invalid(kind, message) => {
+                    self.warn(kind, span, message);
+                    return None;
+                }
+                Operator::Unknown(message) => {
+                    self.warn(DiagnosticKind::UnknownOperator, span, message);
+                    (negated, Node::Term(word))
+                }
+                Operator::Plain => (negated, Node::Term(word)),
+            },
+            _ => return None,
+        };
+
+        Some(Clause {
+            negated,
+            node,
+            span,
+        })
+    }
+}
+
+fn group_span(alternatives: &[Vec<Clause>]) -> Span {
+    alternatives
+        .iter()
+        .flatten()
+        .map(|clause| clause.span)
+        .reduce(Span::to)
+        .unwrap_or_default()
+}
+
+fn parse_operator(word: &str) -> Operator {
+    let Some(idx) = word.find([':', '=', '>', '<']) else {
+        return Operator::Plain;
+    };
+    let name = word[..idx].to_lowercase();
+    let separator_len = if word[idx + 1..].starts_with('=') && !word[idx..].starts_with([':', '='])
+    {
+        2
+    } else {
+        1
+    };
+    let separator = &word[idx..idx + separator_len];
+    let value = &word[idx + separator_len..];
+
+    let op = match separator {
+        ">" => Some(CompareOp::Gt),
+        ">=" => Some(CompareOp::Gte),
+        "<" => Some(CompareOp::Lt),
+        "<=" => Some(CompareOp::Lte),
+        "=" => Some(CompareOp::Eq),
+        _ => None,
+    };
+
+    let parsed = match (name.as_str(), op) {
+        ("by", None) => parse_tag(value).map(|(tag, negated)| (Filter::Author(tag), negated)),
+        ("site", None) => parse_site(value).map(|(tag, negated)| (Filter::Site(tag), negated)),
+        ("type", None) => parse_list(value).map(|types| (Filter::Type(types), false)),
+        ("has", None) => match value.to_lowercase().as_str() {
+            "url" => Some((Filter::Has(TextField::Url), false)),
+            "text" => Some((Filter::Has(TextField::Text), false)),
+            _ => None,
+        },
+        ("is", None) => match value.to_lowercase().as_str() {
+            "top-level" | "toplevel" => Some((Filter::Is(Predicate::TopLevel), false)),
+            _ => None,
+        },
+        ("story" | "thread", None) => parse_id(value).map(|id| (Filter::Thread(id), false)),
+        ("title" | "text" | "url", None) => {
+            let field = match name.as_str() {
+                "title" => TextField::Title,
+                "text" => TextField::Text,
+                _ => TextField::Url,
+            };
+            parse_text(value).map(|text| (Filter::Contains(field, text), false))
+        }
+        ("points", op) => {
+            parse_comparisons(op, value, parse_id).map(|points| (Filter::Points(points), false))
+        }
+        ("comments", op) => parse_comparisons(op, value, parse_id)
+            .map(|comments| (Filter::Comments(comments), false)),
+        ("date", op) => {
+            parse_comparisons(op, value, parse_date).map(|dates| (Filter::Date(dates), false))
+        }
+        ("year", op) => parse_comparisons(op, value, |year| parse_year(year).map(DateValue::Year))
+            .map(|dates| (Filter::Date(dates), false)),
+        // age<48h is the same as date>48h
+        ("age", Some(op)) if op != CompareOp::Eq => parse_relative_date(value).map(|relative| {
+            let op = match op {
+                CompareOp::Gt => CompareOp::Lt,
+                CompareOp::Gte => CompareOp::Lte,
+                CompareOp::Lt => CompareOp::Gt,
+                _ => CompareOp::Gte,
+            };
+            (
+                Filter::Date(vec![Comparison {
+                    op,
+                    value: DateValue::Ago(relative),
+                }]),
+                false,
+            )
+        }),
+        (
+            "by" | "site" | "type" | "story" | "thread" | "title" | "text" | "url" | "has" | "is",
+            Some(_),
+        ) => {
+            return Operator::Unknown(format!(
+                "'{}{}' is not supported, use '{}:'. It was searched for as text.",
+                name, separator, name
+            ))
+        }
+        ("age", _) => {
+            return Operator::Unknown(format!(
+                "'{}{}' is not supported, use '{}>' or '{}<'. It was searched for as text.",
+                name, separator, name, name
+            ))
+        }
+        _ if looks_like_operator(&name, value) => {
+            return Operator::Unknown(format!(
+                "'{}{}' is not a known operator, so it was searched for as text.",
+                name, separator
+            ))
+        }
+        _ => return Operator::Plain,
+    };
+
+    match parsed {
+        Some((filter, negated)) => Operator::Filter { filter, negated },
+        None if matches!(name.as_str(), "date" | "year" | "age") => Operator::Invalid(
+            DiagnosticKind::InvalidDate,
+            format!(
+                "Expected {} after '{}{}', so the filter was ignored.",
+                expected_value(&name),
+                name,
+                separator
+            ),
+        ),
+        None => Operator::Invalid(
+            DiagnosticKind::InvalidValue,
+            format!(
+                "Expected {} after '{}{}', so the filter was ignored.",
+                expected_value(&name),
+                name,
+                separator
+            ),
+        ),
+    }
+}
+
+fn expected_value(name: &str) -> &'static str {
+    match name {
+        "by" => "a username of at least two characters",
+        "site" => "a domain such as github.com",
+        "type" => "a post type such as story or comment, or several separated by commas",
+        "has" => "url or text",
+        "is" => "top-level",
+        "story" | "thread" => "a numeric story ID",
+        "title" | "text" => "a word or quoted phrase",
+        "url" => "part of a URL such as /blog/",
+        "date" => "a date such as 2024-01-31, 2024-01, 2024 or 7d, or a range of them",
+        "year" => "a four digit year or range such as 2015..2019",
+        "age" => "a duration such as 48h, 7d, 2w or 1y",
+        _ => "a whole number or range such as 100..500",
+    }
+}
+
+/// Words such as `autor:pg` or `score>10`, but not URLs or times like `10:30`.
+fn looks_like_operator(name: &str, value: &str) -> bool {
+    name.len() >= 2
+        && name.chars().all(|c| c.is_ascii_lowercase())
+        && !value.is_empty()
+        && !value.starts_with("//")
+}
+
+/// Parses the value of a range operator. With `:` the value may be an inclusive
+/// interval such as `100..500`, `100..` or `..500`, otherwise it must match exactly.
+fn parse_comparisons<T>(
+    op: Option<CompareOp>,
+    value: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Option<Vec<Comparison<T>>> {
+    if let Some(op) = op {
+        return Some(vec![Comparison {
+            op,
+            value: parse(value)?,
+        }]);
+    }
+
+    let Some((lower, upper)) = value.split_once("..") else {
+        return Some(vec![Comparison {
+            op: CompareOp::Eq,
+            value: parse(value)?,
+        }]);
+    };
+    let mut comparisons = vec![];
+    if !lower.is_empty() {
+        comparisons.push(Comparison {
+            op: CompareOp::Gte,
+            value: parse(lower)?,
+        });
+    }
+    if !upper.is_empty() {
+        comparisons.push(Comparison {
+            op: CompareOp::Lte,
+            value: parse(upper)?,
+        });
+    }
+
+    (!comparisons.is_empty()).then_some(comparisons)
+}
+
+/// Parses comma separated tags such as `show,ask`.
+fn parse_list(value: &str) -> Option<Vec<String>> {
+    value
+        .split(',')
+        .map(|tag| is_tag_chars(tag).then(|| tag.to_string()))
+        .collect()
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || c == '-'
+}
+
+fn is_tag_chars(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(is_tag_char)
+}
+
+/// Parses an author or site tag. A leading `-` (as in `by:-dang`) excludes the tag.
+/// Tags cannot start with `-` and included ones must be at least two characters.
+fn parse_tag(value: &str) -> Option<(String, bool)> {
+    if let Some(excluded) = value.strip_prefix('-') {
+        return (is_tag_chars(excluded) && !excluded.starts_with('-'))
+            .then(|| (excluded.to_string(), true));
+    }
+
+    (value.chars().count() >= 2 && is_tag_chars(value)).then(|| (value.to_string(), false))
+}
+
+/// Parses a site tag, which is a host or `github.com/OWNER` as the ingest also tags GitHub
+/// links with the owner of the repository.
+fn parse_site(value: &str) -> Option<(String, bool)> {
+    match value.split_once('/') {
+        Some((host, owner)) => {
+            let (host, negated) = parse_tag(host)?;
+            (host == "github.com" && is_tag_chars(owner))
+                .then(|| (format!("{}/{}", host, owner), negated))
+        }
+        None => parse_tag(value),
+    }
+}
+
+/// Parses the value of a field-scoped text operator, removing quotes around a phrase and
+/// the `\` escaping quotes and backslashes within it.
+fn parse_text(value: &str) -> Option<String> {
+    let value = match value.strip_prefix('"') {
+        Some(quoted) => {
+            let mut text = String::new();
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => match chars.next() {
+                        Some(escaped @ ('"' | '\\')) => text.push(escaped),
+                        Some(other) => {
+                            text.push(c);
+                            text.push(other);
+                        }
+                        None => text.push(c),
+                    },
+                    '"' if chars.as_str().is_empty() => break,
+                    c => text.push(c),
+                }
+            }
+            text
+        }
+        None => value.to_string(),
+    };
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn parse_id(value: &str) -> Option<i64> {
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    value.parse::<i64>().ok()
+}
+
+/// Parses an ISO-8601 day (`2024-01-31`), month (`2024-01`), year (`2024`) or instant
+/// (`2024-01-31T12:00:00Z`), a relative date (`7d`) or the legacy `mm-dd-YYYY` format.
+fn parse_date(value: &str) -> Option<DateValue> {
+    if let Some(relative) = parse_relative_date(value) {
+        return Some(DateValue::Ago(relative));
+    }
+    if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+        return Some(DateValue::Instant(instant.timestamp()));
+    }
+
+    let date_parts = value.split('-').collect::<Vec<&str>>();
+    let lengths = date_parts.iter().map(|part| part.len()).collect::<Vec<_>>();
+    match (date_parts.as_slice(), lengths.as_slice()) {
+        ([year], [4]) => parse_year(year).map(DateValue::Year),
+        ([year, month], [4, 2]) => {
+            let (year, month) = (parse_year(year)?, parse_id(month)? as u32);
+            NaiveDate::from_ymd_opt(year, month, 1).map(|_| DateValue::Month { year, month })
+        }
+        ([year, month, day], [4, 2, 2]) | ([month, day, year], [2, 2, 4]) => {
+            NaiveDate::from_ymd_opt(
+                parse_year(year)?,
+                parse_id(month)? as u32,
+                parse_id(day)? as u32,
+            )
+            .map(DateValue::Day)
+        }
+        _ => None,
+    }
+}
+
+fn parse_year(value: &str) -> Option<i32> {
+    if value.len() != 4 {
+        return None;
+    }
+    parse_id(value).map(|year| year as i32)
+}
+
+/// Parses durations such as `48h`, `7d`, `2w` or `1y`.
+fn parse_relative_date(value: &str) -> Option<RelativeDate> {
+    let unit = match value.chars().last()? {
+        'h' => DateUnit::Hours,
+        'd' => DateUnit::Days,
+        'w' => DateUnit::Weeks,
+        'y' => DateUnit::Years,
+        _ => return None,
+    };
+    let amount = parse_id(&value[..value.len() - 1])?;
+
+    Some(RelativeDate { amount, unit })
+}
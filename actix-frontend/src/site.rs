@@ -0,0 +1,206 @@
+//! Pages about a site, built from sorted listings and counts of the stories submitted
+//! from it.
+
+use crate::{
+    backend::{count_chunks, scroll_all, sorted_chunks, SearchBackend},
+    errors::SearchError,
+    handlers::search_handler::{CustomChunkFilter, CustomConditionType, ScoreChunkMetadata},
+    query::{
+        ast::{Clause, Filter, Node},
+        lower::site_tags,
+        parse,
+    },
+};
+use chrono::{DateTime, Datelike, NaiveDate};
+use futures::{future::try_join_all, try_join};
+use serde::Serialize;
+use std::{cmp::Reverse, collections::BTreeMap};
+use trieve_client::models::{self, FieldCondition, MatchCondition, SortOrder};
+
+/// Most stories of a site read to sum their points and submitters.
+pub const MAX_SITE_STORIES: usize = 10_000;
+
+/// Stories listed on a site page.
+const SITE_TOP_STORIES: usize = 10;
+
+/// Submitters listed on a site page.
+const SITE_TOP_SUBMITTERS: usize = 10;
+
+/// How many stories from the site were submitted in a year.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct YearCount {
+    pub year: i32,
+    pub count: usize,
+    /// Count as a percentage of the busiest year, to draw the timeline without JS.
+    pub percent: usize,
+}
+
+/// A user along with the number and points of the stories they submitted from the site.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubmitterCount {
+    pub by: String,
+    pub count: usize,
+    pub points: i64,
+}
+
+/// What the index knows about the stories from a site.
+#[derive(Debug, Clone, Serialize)]
+pub struct SitePage {
+    pub site: String,
+    pub num_stories: usize,
+    pub total_points: i64,
+    /// Stories per year, from the first year with one to the last.
+    pub timeline: Vec<YearCount>,
+    pub top_submitters: Vec<SubmitterCount>,
+    /// The site has more than [`MAX_SITE_STORIES`] stories, so `total_points` and
+    /// `top_submitters` only count some of them.
+    pub truncated: bool,
+    /// Its stories with the most points.
+    pub top_stories: Vec<ScoreChunkMetadata>,
+}
+
+/// Whether `site` is a host or `github.com/OWNER` that `site:` accepts, so the search of
+/// the page finds the same stories.
+pub fn is_site(site: &str) -> bool {
+    let query = parse(&format!("site:{}", site));
+    query.diagnostics.is_empty()
+        && matches!(
+            query.clauses.as_slice(),
+            [Clause { negated: false, node: Node::Filter(Filter::Site(tag)), .. }] if tag == site
+        )
+}
+
+/// Filter on the stories of `site`, optionally only those submitted in `year`.
+fn site_stories(site: &str, year: Option<i32>) -> CustomChunkFilter {
+    let mut filter = CustomChunkFilter::tagged(&site_tags(site));
+    filter.must_not = Some(Some(vec![CustomConditionType::field_is(
+        "metadata.type",
+        MatchCondition::String("comment".to_string()),
+    )]));
+    if let Some(year) = year {
+        let start_of = |year: i32| {
+            NaiveDate::from_ymd_opt(year, 1, 1)
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map_or(0, |time| time.and_utc().timestamp())
+        };
+        let mut range = models::Range::new();
+        range.gte = Some(Some(Box::new(models::RangeCondition::Integer(start_of(
+            year,
+        )))));
+        range.lt = Some(Some(Box::new(models::RangeCondition::Integer(start_of(
+            year + 1,
+        )))));
+        let mut must = filter.must.take().flatten().unwrap_or_default();
+        must.push(CustomConditionType::FieldCondition(Box::new(
+            FieldCondition {
+                field: "time_stamp".to_string(),
+                match_any: None,
+                match_all: None,
+                date_range: None,
+                geo_bounding_box: None,
+                geo_polygon: None,
+                geo_radius: None,
+                range: Some(Some(Box::new(range))),
+            },
+        )));
+        filter.must = Some(Some(must));
+    }
+    filter
+}
+
+/// Year of the story of `site` first or last by `direction`.
+async fn year_of_story(
+    backend: &dyn SearchBackend,
+    site: &str,
+    direction: SortOrder,
+) -> Result<Option<i32>, SearchError> {
+    let stories = sorted_chunks(
+        backend,
+        site_stories(site, None),
+        "time_stamp",
+        direction,
+        1,
+    )
+    .await?;
+    Ok(stories.first().and_then(|story| {
+        let time = story.chunk.metadata.clone().flatten()?["time"].as_i64()?;
+        DateTime::from_timestamp(time, 0).map(|time| time.year())
+    }))
+}
+
+/// Page of `site`, `None` when none of its stories are in the index.
+pub async fn get_site(
+    backend: &dyn SearchBackend,
+    site: &str,
+) -> Result<Option<SitePage>, SearchError> {
+    let (Some(first_year), Some(last_year)) = (
+        year_of_story(backend, site, SortOrder::Asc).await?,
+        year_of_story(backend, site, SortOrder::Desc).await?,
+    ) else {
+        return Ok(None);
+    };
+    // Each year is counted on its own, all at once along with the rest of the page.
+    let year_counts = try_join_all((first_year..=last_year).map(|year| async move {
+        let count = count_chunks(backend, site_stories(site, Some(year))).await?;
+        Ok::<(i32, usize), SearchError>((year, count))
+    }));
+    let (num_stories, years, stories) = try_join!(
+        count_chunks(backend, site_stories(site, None)),
+        year_counts,
+        scroll_all(backend, site_stories(site, None), MAX_SITE_STORIES),
+    )?;
+    let busiest = years
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(1)
+        .max(1);
+    let timeline = years
+        .into_iter()
+        .map(|(year, count)| YearCount {
+            year,
+            count,
+            percent: count * 100 / busiest,
+        })
+        .collect();
+
+    let mut total_points = 0;
+    let mut submitters: BTreeMap<String, (usize, i64)> = BTreeMap::new();
+    for metadata in stories
+        .chunks
+        .into_iter()
+        .filter_map(|chunk| chunk.metadata.flatten())
+    {
+        let score = metadata["score"].as_i64().unwrap_or_default();
+        total_points += score;
+        let by = metadata["by"].as_str().unwrap_or_default().to_string();
+        let submitter = submitters.entry(by).or_default();
+        submitter.0 += 1;
+        submitter.1 += score;
+    }
+    let mut top_submitters = submitters
+        .into_iter()
+        .map(|(by, (count, points))| SubmitterCount { by, count, points })
+        .collect::<Vec<SubmitterCount>>();
+    top_submitters.sort_by_key(|submitter| Reverse((submitter.count, submitter.points)));
+    top_submitters.truncate(SITE_TOP_SUBMITTERS);
+
+    let top_stories = sorted_chunks(
+        backend,
+        site_stories(site, None),
+        "num_value",
+        SortOrder::Desc,
+        SITE_TOP_STORIES,
+    )
+    .await?;
+
+    Ok(Some(SitePage {
+        site: site.to_string(),
+        num_stories,
+        total_points,
+        timeline,
+        top_submitters,
+        truncated: stories.truncated,
+        top_stories,
+    }))
+}
@@ -0,0 +1,160 @@
+//! Comment threads of stories, rebuilt from the chunks of their comments.
+
+use crate::{
+    backend::{scroll_all, SearchBackend},
+    errors::SearchError,
+    handlers::search_handler::{CustomChunkFilter, CustomConditionType},
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use trieve_client::models::{FieldCondition, MatchCondition};
+
+/// Most comments shown for a thread, the biggest HN threads have a few thousand.
+pub const MAX_THREAD_COMMENTS: usize = 10_000;
+
+/// Deepest reply shown below an item. Deeper ones are linked to instead, as the template
+/// renders each level recursively.
+pub const MAX_THREAD_DEPTH: usize = 100;
+
+/// An item along with its replies, in the order HN ranks them.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ThreadNode {
+    pub metadata: Value,
+    pub children: Vec<ThreadNode>,
+    /// Replies below [`MAX_THREAD_DEPTH`], left out of `children` and shown on the page
+    /// of this item instead.
+    pub hidden_replies: usize,
+}
+
+impl ThreadNode {
+    /// Number of replies to this item, nested ones included.
+    pub fn num_descendants(&self) -> usize {
+        self.hidden_replies
+            + self
+                .children
+                .iter()
+                .map(|child| 1 + child.num_descendants())
+                .sum::<usize>()
+    }
+}
+
+/// Metadata of the comments of a story.
+#[derive(Debug, Clone, Default)]
+pub struct StoryComments {
+    pub comments: Vec<Value>,
+    /// The story has more than [`MAX_THREAD_COMMENTS`] comments, so some are missing.
+    pub truncated: bool,
+}
+
+/// Metadata of all the comments of the story `story_id`, whatever their depth, up to
+/// [`MAX_THREAD_COMMENTS`] of them.
+pub async fn fetch_comments(
+    backend: &dyn SearchBackend,
+    story_id: i64,
+) -> Result<StoryComments, SearchError> {
+    let filters = CustomChunkFilter {
+        must: Some(Some(vec![CustomConditionType::FieldCondition(Box::new(
+            FieldCondition {
+                field: "metadata.top_parent_id".to_string(),
+                match_any: None,
+                match_all: Some(Some(vec![MatchCondition::Integer(story_id)])),
+                date_range: None,
+                geo_bounding_box: None,
+                geo_polygon: None,
+                geo_radius: None,
+                range: None,
+            },
+        ))])),
+        ..Default::default()
+    };
+
+    let scrolled = scroll_all(backend, filters, MAX_THREAD_COMMENTS).await?;
+    Ok(StoryComments {
+        comments: scrolled
+            .chunks
+            .into_iter()
+            .filter_map(|chunk| chunk.metadata.flatten())
+            .collect(),
+        truncated: scrolled.truncated,
+    })
+}
+
+/// Builds the thread below `root` out of `comments`, down to [`MAX_THREAD_DEPTH`]. Replies
+/// are ordered as in the `kids` of their parent, followed by the ones missing from it by
+/// age. Comments which aren't below `root` are left out.
+pub fn build_thread(root: Value, comments: Vec<Value>) -> ThreadNode {
+    let mut replies: HashMap<i64, Vec<Value>> = HashMap::new();
+    for comment in comments {
+        if let Some(parent) = comment["parent"].as_i64() {
+            replies.entry(parent).or_default().push(comment);
+        }
+    }
+    let mut visited = HashSet::new();
+    build_node(root, 0, &mut replies, &mut visited)
+}
+
+/// Number of replies below `id`, which are taken out of `replies`.
+fn count_replies(
+    id: i64,
+    replies: &mut HashMap<i64, Vec<Value>>,
+    visited: &mut HashSet<i64>,
+) -> usize {
+    let mut count = 0;
+    let mut below = vec![id];
+    while let Some(id) = below.pop() {
+        for reply in replies.remove(&id).unwrap_or_default() {
+            let reply_id = reply["id"].as_i64().unwrap_or_default();
+            if visited.insert(reply_id) {
+                count += 1;
+                below.push(reply_id);
+            }
+        }
+    }
+    count
+}
+
+fn build_node(
+    metadata: Value,
+    depth: usize,
+    replies: &mut HashMap<i64, Vec<Value>>,
+    visited: &mut HashSet<i64>,
+) -> ThreadNode {
+    let id = metadata["id"].as_i64().unwrap_or_default();
+    visited.insert(id);
+    if depth >= MAX_THREAD_DEPTH {
+        return ThreadNode {
+            metadata,
+            children: vec![],
+            hidden_replies: count_replies(id, replies, visited),
+        };
+    }
+
+    let kids = metadata["kids"]
+        .as_array()
+        .map(|kids| kids.iter().filter_map(Value::as_i64).collect::<Vec<i64>>())
+        .unwrap_or_default();
+    let rank = |comment: &Value| {
+        let comment_id = comment["id"].as_i64().unwrap_or_default();
+        let position = kids.iter().position(|kid| *kid == comment_id);
+        (
+            position.unwrap_or(usize::MAX),
+            comment["time"].as_i64().unwrap_or_default(),
+        )
+    };
+    let mut children = replies.remove(&id).unwrap_or_default();
+    children.sort_by_key(rank);
+
+    ThreadNode {
+        metadata,
+        children: children
+            .into_iter()
+            .filter_map(|child| {
+                let child_id = child["id"].as_i64().unwrap_or_default();
+                (!visited.contains(&child_id))
+                    .then(|| build_node(child, depth + 1, replies, visited))
+            })
+            .collect(),
+        hidden_replies: 0,
+    }
+}
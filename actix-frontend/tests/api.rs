@@ -0,0 +1,319 @@
+use actix_web::{
+    http::StatusCode,
+    test::{call_and_read_body_json, call_service, init_service, read_body_json, TestRequest},
+    web, App,
+};
+use hn_discovery_webserver::{
+    backend::{circuit_breaker::CircuitBreaker, SearchBackend, TrieveBackend},
+    config::{Config, TrieveConfig},
+    handlers::api_handler,
+    ApiDoc,
+};
+use serde_json::{json, Value};
+use std::{
+    io::{Read, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+use utoipa::OpenApi;
+
+fn config(trieve_api_url: &str) -> Config {
+    Config::from_sources(None, |name| match name {
+        "TRIEVE_API_URL" => Some(trieve_api_url.to_string()),
+        "TRIEVE_API_KEY" => Some("key".to_string()),
+        "TRIEVE_DATASET_ID" => Some("dataset".to_string()),
+        _ => None,
+    })
+    .unwrap()
+}
+
+fn trieve_backend(trieve_api_url: &str) -> web::Data<dyn SearchBackend> {
+    let trieve = TrieveConfig {
+        max_retries: 0,
+        ..config(trieve_api_url).trieve
+    };
+    trieve_backend_with(trieve)
+}
+
+fn trieve_backend_with(trieve: TrieveConfig) -> web::Data<dyn SearchBackend> {
+    let backend: Arc<dyn SearchBackend> = Arc::new(TrieveBackend::from_config(trieve).unwrap());
+    web::Data::from(backend)
+}
+
+#[actix_web::test]
+async fn empty_search_returns_no_results() {
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config("http://127.0.0.1:1")))
+            .app_data(trieve_backend("http://127.0.0.1:1"))
+            .service(api_handler::search),
+    )
+    .await;
+
+    let resp: Value =
+        call_and_read_body_json(&app, TestRequest::get().uri("/api/search?q=").to_request()).await;
+
+    assert_eq!(
+        resp,
+        json!({ "chunks": [], "total_pages": null, "diagnostics": [] })
+    );
+}
+
+#[test]
+fn openapi_documents_search_routes() {
+    let paths = ApiDoc::openapi().paths.paths;
+
+    assert!(paths.contains_key("/"));
+    assert!(paths.contains_key("/api/search"));
+}
+
+#[actix_web::test]
+async fn invalid_params_are_a_bad_request() {
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config("http://127.0.0.1:1")))
+            .app_data(trieve_backend("http://127.0.0.1:1"))
+            .service(api_handler::search),
+    )
+    .await;
+
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/api/search?q=rust&page=-3")
+            .to_request(),
+    )
+    .await;
+
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: Value = read_body_json(resp).await;
+    assert_eq!(body["error"], "invalid_params");
+    assert_eq!(
+        body["message"],
+        "Invalid search: page must be between 1 and 1000, not -3."
+    );
+}
+
+/// Serves a single canned HTTP response in place of Trieve and returns its URL.
+fn fake_trieve(status_line: &'static str, body: &'static str) -> String {
+    fake_trieve_sequence(vec![(status_line, body)]).0
+}
+
+/// Serves the canned HTTP responses in order, one per connection, and returns the URL
+/// along with the number of requests which were answered so far.
+fn fake_trieve_sequence(
+    responses: Vec<(&'static str, &'static str)>,
+) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let served = Arc::new(AtomicUsize::new(0));
+    let counter = served.clone();
+    thread::spawn(move || {
+        for (status_line, body) in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 8192]);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                status_line,
+                body.len(),
+                body
+            );
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+    (url, served)
+}
+
+#[actix_web::test]
+async fn upstream_errors_are_not_empty_results() {
+    for (status_line, body, status, kind) in [
+        (
+            "401 Unauthorized",
+            "{}",
+            StatusCode::BAD_GATEWAY,
+            "auth_failure",
+        ),
+        (
+            "400 Bad Request",
+            "{}",
+            StatusCode::BAD_REQUEST,
+            "bad_request",
+        ),
+        (
+            "500 Internal Server Error",
+            "{}",
+            StatusCode::SERVICE_UNAVAILABLE,
+            "upstream_unavailable",
+        ),
+        (
+            "200 OK",
+            "not json",
+            StatusCode::BAD_GATEWAY,
+            "decode_error",
+        ),
+    ] {
+        let trieve_api_url = fake_trieve(status_line, body);
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(config(&trieve_api_url)))
+                .app_data(trieve_backend(&trieve_api_url))
+                .service(api_handler::search),
+        )
+        .await;
+        let resp = call_service(
+            &app,
+            TestRequest::get().uri("/api/search?q=rust").to_request(),
+        )
+        .await;
+
+        assert_eq!(resp.status(), status, "{}", status_line);
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["error"], kind, "{}", status_line);
+    }
+}
+
+#[actix_web::test]
+async fn transient_failures_are_retried() {
+    let (trieve_api_url, served) = fake_trieve_sequence(vec![
+        ("502 Bad Gateway", "{}"),
+        ("504 Gateway Timeout", "{}"),
+        ("200 OK", r#"{ "chunks": [], "total_pages": 3 }"#),
+    ]);
+    let trieve = TrieveConfig {
+        retry_backoff_ms: 1,
+        ..config(&trieve_api_url).trieve
+    };
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config(&trieve_api_url)))
+            .app_data(trieve_backend_with(trieve))
+            .service(api_handler::search),
+    )
+    .await;
+
+    let resp: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get().uri("/api/search?q=rust").to_request(),
+    )
+    .await;
+
+    assert_eq!(resp["total_pages"], 3);
+    assert_eq!(served.load(Ordering::SeqCst), 3);
+}
+
+#[actix_web::test]
+async fn circuit_breaker_fails_fast_after_repeated_failures() {
+    let (trieve_api_url, served) = fake_trieve_sequence(vec![
+        ("503 Service Unavailable", "{}"),
+        ("503 Service Unavailable", "{}"),
+    ]);
+    let trieve = TrieveConfig {
+        max_retries: 0,
+        circuit_breaker_threshold: 2,
+        ..config(&trieve_api_url).trieve
+    };
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config(&trieve_api_url)))
+            .app_data(trieve_backend_with(trieve))
+            .service(api_handler::search),
+    )
+    .await;
+
+    for kind in [
+        "upstream_unavailable",
+        "upstream_unavailable",
+        "circuit_open",
+    ] {
+        let resp = call_service(
+            &app,
+            TestRequest::get().uri("/api/search?q=rust").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body: Value = read_body_json(resp).await;
+        assert_eq!(body["error"], kind);
+    }
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn circuit_breaker_lets_a_single_probe_through_after_the_cooldown() {
+    let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+    breaker.record_failure();
+    assert!(!breaker.allows_call());
+
+    thread::sleep(Duration::from_millis(30));
+    assert!(breaker.allows_call());
+    assert!(!breaker.allows_call());
+
+    // A failed probe opens the breaker for another cooldown...
+    breaker.record_failure();
+    assert!(!breaker.allows_call());
+    thread::sleep(Duration::from_millis(30));
+    assert!(breaker.allows_call());
+    assert!(!breaker.allows_call());
+
+    // ...while a successful one closes it.
+    breaker.record_success();
+    assert!(breaker.allows_call());
+    assert!(breaker.allows_call());
+}
+
+#[test]
+fn config_env_overrides_file_and_is_validated() {
+    let file = r#"
+        bind_address = "127.0.0.1:8080"
+
+        [trieve]
+        api_url = "https://file.example"
+        api_key = "file-key"
+        dataset_id = "file-dataset"
+
+        [search]
+        default_search_type = "hybrid"
+        max_page_size = 50
+    "#;
+    let config = Config::from_sources(Some(file), |name| match name {
+        "TRIEVE_API_KEY" => Some("env-key".to_string()),
+        "TRIEVE_CIRCUIT_BREAKER_COOLDOWN_MS" => Some("5000".to_string()),
+        _ => None,
+    })
+    .unwrap();
+    assert_eq!(config.bind_address, "127.0.0.1:8080");
+    assert_eq!(config.trieve.api_url, "https://file.example");
+    assert_eq!(config.trieve.api_key, "env-key");
+    assert_eq!(config.trieve.circuit_breaker_cooldown_ms, 5000);
+    assert_eq!(config.search.max_page_size, 50);
+    assert_eq!(config.search.default_page_size, 30);
+
+    let missing = Config::from_sources(None, |_| None).unwrap_err();
+    assert_eq!(
+        missing.to_string(),
+        "TRIEVE_API_URL must be set, either as an env var or in the config file"
+    );
+
+    let invalid = Config::from_sources(Some(file), |name| {
+        (name == "MAX_PAGE_SIZE").then(|| "lots".to_string())
+    })
+    .unwrap_err();
+    assert_eq!(
+        invalid.to_string(),
+        "MAX_PAGE_SIZE is invalid: invalid digit found in string"
+    );
+
+    let no_timeout = Config::from_sources(Some(file), |name| {
+        (name == "TRIEVE_READ_TIMEOUT_MS").then(|| "0".to_string())
+    })
+    .unwrap_err();
+    assert_eq!(
+        no_timeout.to_string(),
+        "TRIEVE_READ_TIMEOUT_MS is invalid: it must be at least 1"
+    );
+}
@@ -0,0 +1,188 @@
+mod common;
+
+use actix_web::{
+    http::{header, StatusCode},
+    test::{call_and_read_body_json, call_service, init_service, TestRequest},
+    web, App,
+};
+use async_trait::async_trait;
+use common::{backend, memory_config};
+use hn_discovery_webserver::{
+    backend::{
+        cache::{CacheStats, CacheStatsSnapshot, CacheStore, MemoryCache},
+        CachedBackend, SearchBackend,
+    },
+    handlers::{api_handler, page_handler, search_handler::cache_period_start},
+    query::{lower::lower_at, parse},
+    templates,
+    thread::fetch_comments,
+};
+use serde_json::Value;
+use std::{sync::Arc, thread, time::Duration};
+
+fn cached_backend(stats: Arc<CacheStats>) -> web::Data<dyn SearchBackend> {
+    let inner = Arc::new(backend());
+    let store = Box::new(MemoryCache::new(10, Duration::from_secs(60)));
+    let backend: Arc<dyn SearchBackend> =
+        Arc::new(CachedBackend::new(inner, store, stats, "fixtures"));
+    web::Data::from(backend)
+}
+
+#[actix_web::test]
+async fn memory_cache_evicts_least_recently_used_and_expired_entries() {
+    let cache = MemoryCache::new(2, Duration::from_secs(60));
+    cache.set("a", "1".to_string()).await;
+    cache.set("b", "2".to_string()).await;
+    assert_eq!(cache.get("a").await.as_deref(), Some("1"));
+    cache.set("c", "3".to_string()).await;
+    assert_eq!(cache.get("b").await, None);
+    assert_eq!(cache.get("a").await.as_deref(), Some("1"));
+    assert_eq!(cache.get("c").await.as_deref(), Some("3"));
+
+    let cache = MemoryCache::new(2, Duration::from_millis(10));
+    cache.set("a", "1".to_string()).await;
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(cache.get("a").await, None);
+}
+
+#[actix_web::test]
+async fn repeated_searches_are_answered_from_the_cache() {
+    let stats = Arc::new(CacheStats::default());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(memory_config()))
+            .app_data(cached_backend(stats.clone()))
+            .app_data(web::Data::from(stats.clone()))
+            .service(api_handler::search)
+            .service(api_handler::cache_stats),
+    )
+    .await;
+
+    let mut responses = vec![];
+    for uri in [
+        "/api/search?q=search",
+        "/api/search?q=search",
+        "/api/search?q=search&order_by=points",
+    ] {
+        let resp: Value =
+            call_and_read_body_json(&app, TestRequest::get().uri(uri).to_request()).await;
+        responses.push(resp);
+    }
+    assert_eq!(responses[0], responses[1]);
+
+    let snapshot: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get().uri("/api/cache_stats").to_request(),
+    )
+    .await;
+    assert_eq!(snapshot, serde_json::json!({ "hits": 1, "misses": 2 }));
+    assert_eq!(stats.snapshot(), CacheStatsSnapshot { hits: 1, misses: 2 });
+}
+
+#[actix_web::test]
+async fn threads_are_scrolled_from_the_cache() {
+    let stats = Arc::new(CacheStats::default());
+    let backend = cached_backend(stats.clone());
+
+    let first = fetch_comments(backend.get_ref(), 1001).await.unwrap();
+    let second = fetch_comments(backend.get_ref(), 1001).await.unwrap();
+    assert_eq!(first.comments, second.comments);
+    assert_eq!(stats.snapshot(), CacheStatsSnapshot { hits: 1, misses: 1 });
+}
+
+/// A store which several backends share, like the Redis of the ingest.
+struct SharedCache(Arc<MemoryCache>);
+
+#[async_trait]
+impl CacheStore for SharedCache {
+    async fn get(&self, key: &str) -> Option<String> {
+        self.0.get(key).await
+    }
+
+    async fn set(&self, key: &str, value: String) {
+        self.0.set(key, value).await
+    }
+}
+
+#[actix_web::test]
+async fn datasets_sharing_a_store_keep_their_own_keys() {
+    let shared = Arc::new(MemoryCache::new(10, Duration::from_secs(60)));
+    let stats = Arc::new(CacheStats::default());
+    let cached = |dataset: &str| {
+        let store = Box::new(SharedCache(shared.clone()));
+        CachedBackend::new(Arc::new(backend()), store, stats.clone(), dataset)
+    };
+
+    let hn = cached("hn");
+    assert!(hn
+        .cache_key("count", &1001)
+        .starts_with("hn-discovery:v1:hn:count:"));
+    fetch_comments(&hn, 1001).await.unwrap();
+    fetch_comments(&cached("lobsters"), 1001).await.unwrap();
+    assert_eq!(stats.snapshot(), CacheStatsSnapshot { hits: 0, misses: 2 });
+    fetch_comments(&cached("hn"), 1001).await.unwrap();
+    assert_eq!(stats.snapshot(), CacheStatsSnapshot { hits: 1, misses: 2 });
+}
+
+#[actix_web::test]
+async fn search_pages_have_an_etag_and_max_age() {
+    let config = memory_config();
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(cached_backend(Arc::new(CacheStats::default())))
+            .app_data(web::Data::new(config))
+            .service(page_handler::homepage),
+    )
+    .await;
+
+    let resp = call_service(&app, TestRequest::get().uri("/?q=search").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get(header::CACHE_CONTROL).unwrap(),
+        "public, max-age=60"
+    );
+    let etag = resp.headers().get(header::ETAG).unwrap().clone();
+
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/?q=search")
+            .insert_header((header::IF_NONE_MATCH, etag.clone()))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers().get(header::ETAG), Some(&etag));
+
+    let resp = call_service(
+        &app,
+        TestRequest::get()
+            .uri("/?q=search&page=0")
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(
+        resp.headers().get(header::CACHE_CONTROL).unwrap(),
+        "no-store"
+    );
+}
+
+#[test]
+fn relative_dates_keep_the_payload_for_the_cache_period() {
+    let filters_at = |now: i64| lower_at(&parse("rust date>7d"), now).chunk_filter();
+    let start = cache_period_start(1_700_000_000, 60);
+    assert_eq!(start, 1_699_999_980);
+    assert_eq!(cache_period_start(1_700_000_039, 60), start);
+    assert_eq!(
+        filters_at(start),
+        filters_at(cache_period_start(1_700_000_030, 60))
+    );
+    assert_ne!(
+        filters_at(start),
+        filters_at(cache_period_start(1_700_000_040, 60))
+    );
+    assert_eq!(cache_period_start(1_700_000_001, 0), 1_700_000_001);
+}
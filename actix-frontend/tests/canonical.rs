@@ -0,0 +1,234 @@
+use chrono::NaiveDate;
+use hn_discovery_webserver::query::{
+    ast::{
+        Clause, CompareOp, Comparison, DateUnit, DateValue, Filter, Node, Predicate, RelativeDate,
+        Span, TextField,
+    },
+    canonical::{canonicalize, serialize},
+    parse,
+};
+use hn_discovery_webserver::{config::SearchConfig, handlers::page_handler::SearchQueryParams};
+use proptest::prelude::*;
+
+fn strip_spans(clauses: Vec<Clause>) -> Vec<Clause> {
+    clauses
+        .into_iter()
+        .map(|clause| Clause {
+            span: Span::default(),
+            node: match clause.node {
+                Node::Group(alternatives) => {
+                    Node::Group(alternatives.into_iter().map(strip_spans).collect())
+                }
+                node => node,
+            },
+            ..clause
+        })
+        .collect()
+}
+
+fn canonical(query: &str) -> String {
+    serialize(&canonicalize(&parse(query).clauses))
+}
+
+fn word() -> impl Strategy<Value = String> {
+    "[a-z][a-z0-9]{0,7}"
+}
+
+fn words() -> impl Strategy<Value = String> {
+    prop::collection::vec(word(), 1..4).prop_map(|words| words.join(" "))
+}
+
+/// Value of `title:`, `text:` or `url:`, including the characters which have to be quoted.
+fn text_value() -> impl Strategy<Value = String> {
+    prop_oneof![
+        words(),
+        r#"[a-z0-9/.()"\\]([a-z0-9/.()"\\ ]{0,10}[a-z0-9/.()"\\])?"#,
+    ]
+}
+
+fn tag() -> impl Strategy<Value = String> {
+    "[a-z0-9_.][a-z0-9_.-]{0,10}"
+}
+
+fn comparisons<T: Clone + std::fmt::Debug + 'static>(
+    value: impl Strategy<Value = T> + Clone + 'static,
+) -> impl Strategy<Value = Vec<Comparison<T>>> {
+    let op = prop_oneof![
+        Just(CompareOp::Gt),
+        Just(CompareOp::Gte),
+        Just(CompareOp::Lt),
+        Just(CompareOp::Lte),
+        Just(CompareOp::Eq),
+    ];
+    prop_oneof![
+        (op, value.clone()).prop_map(|(op, value)| vec![Comparison { op, value }]),
+        (value.clone(), value).prop_map(|(lower, upper)| vec![
+            Comparison {
+                op: CompareOp::Gte,
+                value: lower,
+            },
+            Comparison {
+                op: CompareOp::Lte,
+                value: upper,
+            },
+        ]),
+    ]
+}
+
+fn date_value() -> impl Strategy<Value = DateValue> + Clone {
+    let unit = prop_oneof![
+        Just(DateUnit::Hours),
+        Just(DateUnit::Days),
+        Just(DateUnit::Weeks),
+        Just(DateUnit::Years),
+    ];
+    prop_oneof![
+        (1970..2100i32, 1..=12u32, 1..=28u32).prop_map(|(year, month, day)| DateValue::Day(
+            NaiveDate::from_ymd_opt(year, month, day).unwrap()
+        )),
+        (1970..2100i32, 1..=12u32).prop_map(|(year, month)| DateValue::Month { year, month }),
+        (1000..10000i32).prop_map(DateValue::Year),
+        (0..4_000_000_000i64).prop_map(DateValue::Instant),
+        (0..1000i64, unit).prop_map(|(amount, unit)| DateValue::Ago(RelativeDate { amount, unit })),
+    ]
+}
+
+fn filter() -> impl Strategy<Value = Filter> {
+    let field = prop_oneof![Just(TextField::Title), Just(TextField::Text)];
+    prop_oneof![
+        tag().prop_map(Filter::Author),
+        tag().prop_map(Filter::Site),
+        prop::collection::vec(tag(), 1..4).prop_map(Filter::Type),
+        (0..100_000_000i64).prop_map(Filter::Thread),
+        (field, text_value()).prop_map(|(field, text)| Filter::Contains(field, text)),
+        text_value().prop_map(|url| Filter::Contains(TextField::Url, url)),
+        prop_oneof![Just(TextField::Url), Just(TextField::Text)].prop_map(Filter::Has),
+        Just(Filter::Is(Predicate::TopLevel)),
+        comparisons(0..1_000_000i64).prop_map(Filter::Points),
+        comparisons(0..1_000_000i64).prop_map(Filter::Comments),
+        comparisons(date_value()).prop_map(Filter::Date),
+    ]
+}
+
+fn clause() -> impl Strategy<Value = Clause> {
+    let leaf = (
+        any::<bool>(),
+        prop_oneof![
+            word().prop_map(Node::Term),
+            words().prop_map(Node::Phrase),
+            filter().prop_map(Node::Filter),
+        ],
+    )
+        .prop_map(|(negated, node)| Clause {
+            negated,
+            node,
+            span: Span::default(),
+        });
+
+    leaf.prop_recursive(3, 24, 4, |inner| {
+        (
+            any::<bool>(),
+            prop::collection::vec(prop::collection::vec(inner, 1..4), 2..4),
+        )
+            .prop_map(|(negated, alternatives)| Clause {
+                negated,
+                node: Node::Group(alternatives),
+                span: Span::default(),
+            })
+    })
+}
+
+/// Space separated mix of words, operators and syntax, with and without mistakes.
+fn query_text() -> impl Strategy<Value = String> {
+    let token = prop_oneof![
+        word(),
+        Just("OR".to_string()),
+        Just("(".to_string()),
+        Just(")".to_string()),
+        Just("-".to_string()),
+        "-?(by|site|type|thread|story|title|has|is|points|comments|date|age|year)[:<>=]{1,2}[a-z0-9.,-]{0,6}",
+        "\"[a-z ]{0,8}\"",
+    ];
+    prop::collection::vec((token, "[ ()-]?"), 0..10).prop_map(|tokens| {
+        tokens
+            .into_iter()
+            .map(|(token, glue)| format!("{}{}", token, glue))
+            .collect::<Vec<String>>()
+            .join(" ")
+    })
+}
+
+proptest! {
+    #[test]
+    fn parse_serialize_round_trips(clauses in prop::collection::vec(clause(), 1..6)) {
+        let clauses = canonicalize(&clauses);
+        let query = serialize(&clauses);
+        let parsed = parse(&query);
+
+        prop_assert!(parsed.diagnostics.is_empty(), "{}: {:?}", query, parsed.diagnostics);
+        prop_assert_eq!(strip_spans(parsed.clauses), clauses);
+    }
+
+    #[test]
+    fn canonical_form_is_stable(query in query_text()) {
+        // Queries with diagnostics are never rewritten.
+        prop_assume!(parse(&query).diagnostics.is_empty());
+        let once = canonical(&query);
+        prop_assert_eq!(canonical(&once), once);
+    }
+}
+
+#[test]
+fn equivalent_queries_share_a_canonical_form() {
+    assert_eq!(
+        canonical("  points>100   by:pg rust "),
+        canonical("rust by:pg points>100")
+    );
+    assert_eq!(canonical("rust by:-dang"), "rust -by:dang");
+    assert_eq!(canonical("type:show,ask rust"), "rust type:ask,show");
+    assert_eq!(canonical("rust story:123"), "rust thread:123");
+    assert_eq!(canonical("rust age<48h"), "rust date>48h");
+    assert_eq!(canonical("rust by:pg by:pg"), "rust by:pg");
+    assert_eq!(canonical("(rust by:pg)"), "rust by:pg");
+    assert_eq!(canonical("rust OR go"), "rust OR go");
+    assert_eq!(
+        canonical(r#"(title:"f(x)" OR by:pg)"#),
+        r#"title:"f(x)" OR by:pg"#
+    );
+    assert_eq!(
+        canonical(r#"url:"say \"hi\"" title:C:\dir"#),
+        r#"title:C:\dir url:"say \"hi\"""#
+    );
+}
+
+fn params(q: &str, post_type: Option<&str>) -> SearchQueryParams {
+    SearchQueryParams {
+        q: Some(q.to_string()),
+        page: Some(1),
+        page_size: None,
+        order_by: Some("relevance".to_string()),
+        search_type: Some("semantic".to_string()),
+        post_type: post_type.map(str::to_string),
+    }
+}
+
+#[test]
+fn inline_type_and_post_type_share_a_url() {
+    let inline = params("type:show  rust", Some("all"))
+        .canonical(&SearchConfig::default())
+        .unwrap();
+    let selected = params("rust", Some("show"))
+        .canonical(&SearchConfig::default())
+        .unwrap();
+
+    assert_eq!(inline, selected);
+    assert_eq!(inline.url(), "/?q=rust&search_type=semantic&post_type=show");
+    assert_eq!(
+        params("rust type:show", Some("show")).canonical(&SearchConfig::default()),
+        Some(selected)
+    );
+    assert_eq!(
+        params("rust points>abc", None).canonical(&SearchConfig::default()),
+        None
+    );
+}
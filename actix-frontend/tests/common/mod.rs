@@ -0,0 +1,39 @@
+//! Fixtures shared by the integration tests, each of which only uses some of them.
+#![allow(dead_code)]
+
+use hn_discovery_webserver::{
+    backend::{memory::item_to_chunk, InMemoryBackend},
+    config::{BackendKind, Config},
+};
+use serde_json::Value;
+use std::path::Path;
+use trieve_client::models::ChunkMetadata;
+
+/// Backend over the items of `fixtures/hn_items.json`.
+pub fn backend() -> InMemoryBackend {
+    InMemoryBackend::from_file(Path::new("fixtures/hn_items.json")).unwrap()
+}
+
+/// Config of the memory backend, with `vars` set on top of the defaults.
+pub fn memory_config_with(vars: &[(&str, &str)]) -> Config {
+    let config = Config::from_sources(None, |name| match name {
+        "SEARCH_BACKEND" => Some("memory".to_string()),
+        _ => vars
+            .iter()
+            .find(|(var, _)| *var == name)
+            .map(|(_, value)| value.to_string()),
+    })
+    .unwrap();
+    assert_eq!(config.search_backend, BackendKind::Memory);
+    config
+}
+
+/// Config of the memory backend.
+pub fn memory_config() -> Config {
+    memory_config_with(&[])
+}
+
+/// Chunk of the HN item `item`, as ingest would write it.
+pub fn item(item: Value) -> ChunkMetadata {
+    item_to_chunk(&serde_json::from_value(item).unwrap(), None)
+}
@@ -0,0 +1,70 @@
+use hn_discovery_webserver::{config::Config, formatting::sanitize_html, templates};
+use minijinja::context;
+use serde_json::json;
+
+fn sanitize(html: &str) -> String {
+    sanitize_html(html).to_string()
+}
+
+#[test]
+fn hn_markup_is_kept() {
+    assert_eq!(
+        sanitize("First<p>Second with <i>italics</i> and <code>code</code><p><pre><code>  indented\n</code></pre>"),
+        "First<p>Second with <i>italics</i> and <code>code</code></p><p></p><pre><code>  indented\n</code></pre>"
+    );
+    assert_eq!(
+        sanitize(
+            r#"See <a href="https:&#x2F;&#x2F;example.com&#x2F;a?b=1&amp;c=2" rel="nofollow">example.com</a>"#
+        ),
+        r#"See <a href="https://example.com/a?b=1&amp;c=2" rel="nofollow noopener noreferrer">example.com</a>"#
+    );
+    assert_eq!(
+        sanitize(r#"<a href="item?id=1001">thread</a>"#),
+        r#"<a href="https://news.ycombinator.com/item?id=1001" rel="nofollow noopener noreferrer">thread</a>"#
+    );
+}
+
+#[test]
+fn scripts_and_handlers_are_removed() {
+    assert_eq!(sanitize("Hi<script>alert(1)</script>!"), "Hi!");
+    assert_eq!(
+        sanitize(r#"<img src=x onerror="alert(1)"><i onclick="alert(1)">hi</i>"#),
+        "<i>hi</i>"
+    );
+    assert_eq!(
+        sanitize(r#"<a href="javascript:alert(1)" onmouseover="alert(1)">x</a>"#),
+        r#"<a rel="nofollow noopener noreferrer">x</a>"#
+    );
+    assert_eq!(
+        sanitize(
+            r#"<svg onload=alert(1)><style>*{}</style><iframe src="https://evil.example"></iframe>"#
+        ),
+        ""
+    );
+    assert_eq!(
+        sanitize("&lt;script&gt;alert(1)&lt;/script&gt;"),
+        "&lt;script&gt;alert(1)&lt;/script&gt;"
+    );
+}
+
+#[test]
+fn search_results_render_sanitized_text() {
+    let env = templates(&Config::default());
+    let html = env
+        .get_template("components/searchresult.html")
+        .unwrap()
+        .render(context! {
+            result => json!({
+                "score": 0.0,
+                "chunk": {
+                    "chunk_html": "<i>Nice</i><img src=x onerror=alert(1)><script>alert(2)</script> \n\n",
+                    "metadata": { "id": 1, "by": "pg", "time": 0, "score": 1 },
+                },
+            }),
+        })
+        .unwrap();
+
+    assert!(html.contains("<i>Nice</i>"));
+    assert!(!html.contains("onerror"));
+    assert!(!html.contains("<script>"));
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use actix_web::{
+    test::{call_and_read_body_json, init_service, TestRequest},
+    web, App,
+};
+use common::{backend, memory_config_with};
+use hn_discovery_webserver::{
+    backend::SearchBackend,
+    handlers::api_handler,
+    highlight::{mark_terms, plain_text, query_terms, snippets},
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+#[test]
+
+fn query_terms_skip_negated_words_and_phrases() {
+    assert_eq!(
+        query_terms(r#"Rust "search engine" -postgres -"full text" rust"#),
+        vec!["search engine", "rust"]
+    );
+}
+
+#[test]
+fn marked_terms_are_the_only_html() {
+    let terms = query_terms("script rust");
+    assert_eq!(
+        mark_terms("<script>alert('Rust')</script> trust", &terms),
+        "&lt;<mark>script</mark>&gt;alert(&#x27;<mark>Rust</mark>&#x27;)&lt;/<mark>script</mark>&gt; trust"
+    );
+    assert_eq!(
+        plain_text(
+            "I&#x27;d use <i>Rust</i>.<p>See <a href=\"https:&#x2F;&#x2F;x.org\">x</a> &amp; co"
+        ),
+        "I'd use Rust. See x & co"
+    );
+}
+
+#[test]
+fn snippets_prefer_trieve_highlights_and_fall_back_to_windows() {
+    let terms = query_terms("index");
+    let highlights = vec!["the <mark><b>index</b></mark> is memory mapped".to_string()];
+    assert_eq!(
+        snippets(&highlights, "ignored", &terms, 2),
+        vec!["the <mark>index</mark> is memory mapped"]
+    );
+
+    let text = "Thanks! The index is memory mapped, and the index is small.";
+    assert_eq!(
+        snippets(&[], text, &terms, 2),
+        vec![
+            "Thanks! The <mark>index</mark> is memory…",
+            "…and the <mark>index</mark> is small."
+        ]
+    );
+    assert!(snippets(&[], text, &query_terms("postgres"), 2).is_empty());
+}
+
+#[actix_web::test]
+async fn api_results_have_snippets() {
+    let config = memory_config_with(&[("SNIPPET_WINDOW", "3")]);
+    // Only the snippets widen, Trieve's highlights keep their window.
+    assert_eq!(config.search.highlight.window, 0);
+    let backend: Arc<dyn SearchBackend> = Arc::new(backend());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::from(backend))
+            .service(api_handler::search),
+    )
+    .await;
+
+    let resp: Value = call_and_read_body_json(
+        &app,
+        TestRequest::get()
+            .uri("/api/search?q=postgres+engine&order_by=date")
+            .to_request(),
+    )
+    .await;
+    let snippets = resp["chunks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|chunk| {
+            (
+                chunk["chunk"]["metadata"]["id"].clone(),
+                chunk["snippets"].clone(),
+            )
+        })
+        .collect::<Vec<(Value, Value)>>();
+    assert_eq!(
+        snippets,
+        vec![
+            (json!(1040), Value::Null),
+            (json!(1001), Value::Null),
+            (
+                json!(1021),
+                json!([
+                    "<mark>Postgres</mark> tsvector got us…",
+                    "…needed a search <mark>engine</mark>."
+                ])
+            ),
+            (
+                json!(1020),
+                json!([
+                    "…LIKE queries in <mark>Postgres</mark> and are looking…",
+                    "…at dedicated search <mark>engine</mark>s."
+                ])
+            ),
+        ]
+    );
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body, TestRequest},
+    web, App,
+};
+use common::{backend, memory_config};
+use hn_discovery_webserver::{
+    backend::{memory::HnItem, scroll_all, InMemoryBackend, ScrollChunksPayload, SearchBackend},
+    handlers::{item_handler, search_handler::CustomChunkFilter},
+    templates,
+    thread::{build_thread, fetch_comments, ThreadNode, MAX_THREAD_DEPTH},
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+fn ids(node: &ThreadNode) -> Value {
+    json!([
+        node.metadata["id"],
+        node.children.iter().map(ids).collect::<Vec<Value>>()
+    ])
+}
+
+#[test]
+fn threads_follow_kids_then_age() {
+    let comment = |id: i64, parent: i64, time: i64, kids: &[i64]| json!({ "id": id, "parent": parent, "time": time, "kids": kids });
+    let thread = build_thread(
+        json!({ "id": 1, "kids": [3, 2] }),
+        vec![
+            comment(2, 1, 10, &[4]),
+            comment(3, 1, 20, &[]),
+            comment(4, 2, 30, &[]),
+            comment(5, 1, 5, &[]),
+            comment(6, 99, 5, &[]),
+        ],
+    );
+
+    assert_eq!(ids(&thread), json!([1, [[3, []], [2, [[4, []]]], [5, []]]]));
+    assert_eq!(thread.num_descendants(), 4);
+}
+
+#[actix_web::test]
+async fn comments_are_scrolled_by_story() {
+    let backend = backend();
+
+    let page = backend
+        .scroll(&ScrollChunksPayload {
+            filters: None,
+            offset_chunk_id: None,
+            page_size: 2,
+            sort_by: None,
+        })
+        .await
+        .unwrap();
+    let next_page = backend
+        .scroll(&ScrollChunksPayload {
+            filters: None,
+            offset_chunk_id: Some(page[1].id),
+            page_size: 2,
+            sort_by: None,
+        })
+        .await
+        .unwrap();
+    assert_eq!(next_page[0].id, page[1].id);
+
+    let story_comments = fetch_comments(&backend, 1001).await.unwrap();
+    assert!(!story_comments.truncated);
+    let mut comment_ids = story_comments
+        .comments
+        .iter()
+        .map(|comment| comment["id"].as_i64().unwrap())
+        .collect::<Vec<i64>>();
+    comment_ids.sort();
+    assert_eq!(comment_ids, vec![1002, 1003, 1004]);
+
+    let filters = CustomChunkFilter::tagged(&["comment".to_string()]);
+    let scrolled = scroll_all(&backend, filters.clone(), 2).await.unwrap();
+    assert_eq!(scrolled.chunks.len(), 2);
+    assert!(scrolled.truncated);
+    let scrolled = scroll_all(&backend, filters, 6).await.unwrap();
+    assert_eq!(scrolled.chunks.len(), 6);
+    assert!(!scrolled.truncated);
+}
+
+#[actix_web::test]
+async fn item_pages_render_the_thread() {
+    let config = memory_config();
+    let backend: Arc<dyn SearchBackend> = Arc::new(backend());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(web::Data::from(backend))
+            .app_data(web::Data::new(config))
+            .service(item_handler::item),
+    )
+    .await;
+    let get = |uri: &str| TestRequest::get().uri(uri).to_request();
+
+    let resp = call_service(&app, get("/item/1001")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("Writing a search engine in Rust"));
+    assert!(body.contains("3 comment(s)"));
+    let positions = ["1002", "1003", "1004"]
+        .map(|id| body.find(&format!(r#"<details open id="{}""#, id)).unwrap());
+    assert!(positions[0] < positions[1] && positions[1] < positions[2]);
+
+    let resp = call_service(&app, get("/item/1002")).await;
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("on: Writing a search engine in Rust"));
+    assert!(body.contains(r#"<details open id="1003""#));
+    assert!(!body.contains(r#"<details open id="1004""#));
+
+    let resp = call_service(&app, get("/item/42")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn deep_threads_link_to_their_deepest_replies() {
+    let depth = 600;
+    let comments = (2..=depth + 1)
+        .map(|id| json!({ "id": id, "parent": id - 1, "time": id, "kids": [id + 1] }))
+        .collect::<Vec<Value>>();
+    let thread = build_thread(json!({ "id": 1, "kids": [2] }), comments);
+    let mut deepest = &thread;
+    for _ in 0..MAX_THREAD_DEPTH {
+        deepest = &deepest.children[0];
+    }
+    assert!(deepest.children.is_empty());
+    assert_eq!(deepest.hidden_replies, depth as usize - MAX_THREAD_DEPTH);
+    assert_eq!(thread.num_descendants(), depth as usize);
+
+    let items = std::iter::once(json!({ "id": 1, "type": "story", "by": "ada", "time": 1, "title": "Deep" }))
+        .chain((2..=depth + 1).map(|id| {
+            json!({ "id": id, "type": "comment", "by": "bob", "time": id, "text": "Reply", "parent": id - 1 })
+        }))
+        .map(|item| serde_json::from_value::<HnItem>(item).unwrap())
+        .collect();
+    let config = memory_config();
+    let backend: Arc<dyn SearchBackend> = Arc::new(InMemoryBackend::from_items(items));
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(web::Data::from(backend))
+            .app_data(web::Data::new(config))
+            .service(item_handler::item),
+    )
+    .await;
+
+    let resp = call_service(&app, TestRequest::get().uri("/item/1").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("600 comment(s)"));
+    assert!(body.contains(&format!(r#"href="/item/{}""#, MAX_THREAD_DEPTH + 1)));
+    assert!(body.contains(&format!(
+        "{} more replies",
+        depth as usize - MAX_THREAD_DEPTH
+    )));
+}
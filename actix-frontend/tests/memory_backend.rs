@@ -0,0 +1,218 @@
+mod common;
+
+use actix_web::{
+    test::{call_and_read_body, call_and_read_body_json, init_service, TestRequest},
+    web, App,
+};
+use common::{backend, memory_config};
+use hn_discovery_webserver::{
+    backend::{
+        count_chunks, sorted_chunks, CountChunksPayload, GroupSearchPayload,
+        RecommendChunksPayload, SearchBackend,
+    },
+    handlers::{
+        api_handler, page_handler,
+        search_handler::{CustomChunkFilter, ScoreChunkMetadata},
+    },
+    templates,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use trieve_client::models::{CountSearchMethod, SearchMethod, SortOrder};
+
+/// Response of `/api/search` for the given query string.
+async fn search(query_string: &str) -> Value {
+    let backend: Arc<dyn SearchBackend> = Arc::new(backend());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(memory_config()))
+            .app_data(web::Data::from(backend))
+            .service(api_handler::search),
+    )
+    .await;
+
+    call_and_read_body_json(
+        &app,
+        TestRequest::get()
+            .uri(&format!("/api/search?{}", query_string))
+            .to_request(),
+    )
+    .await
+}
+
+/// Ids of the items returned by `/api/search` for the given query string.
+async fn search_ids(query_string: &str) -> Vec<i64> {
+    search(query_string).await["chunks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|chunk| chunk["chunk"]["metadata"]["id"].as_i64().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn inline_filters_apply_to_fixtures() {
+    assert_eq!(search_ids("q=search+by%3Aquill").await, vec![1020, 1011]);
+    assert_eq!(search_ids("q=search+points%3E100").await, vec![1001, 1020]);
+    assert_eq!(search_ids("q=search+site%3Agithub.com").await, vec![1010]);
+    assert_eq!(search_ids("q=search&post_type=job").await, vec![1040]);
+    assert_eq!(
+        search_ids("q=index+thread%3A1001&post_type=comment").await,
+        vec![1002, 1003]
+    );
+    assert_eq!(
+        search_ids("q=search+-postgres+date%3E2023-06-01")
+            .await
+            .len(),
+        4
+    );
+}
+
+#[actix_web::test]
+async fn results_can_be_ordered_and_paged() {
+    assert_eq!(
+        search_ids("q=search+type%3Astory&order_by=points").await,
+        vec![1001, 1020, 1010]
+    );
+    assert_eq!(
+        search_ids("q=search+type%3Astory&order_by=points&page=2&page_size=2").await,
+        vec![1010]
+    );
+}
+
+#[actix_web::test]
+async fn chunks_recommendations_and_groups() {
+    let backend = backend();
+
+    let chunk = backend.get_chunk("1003").await.unwrap().unwrap();
+    let metadata = chunk.metadata.flatten().unwrap();
+    assert_eq!(metadata["top_parent_id"], 1001);
+    assert_eq!(metadata["parent_title"], "Writing a search engine in Rust");
+    assert_eq!(backend.get_chunk("42").await.unwrap(), None);
+
+    let recommended = backend
+        .recommend(&RecommendChunksPayload {
+            positive_tracking_ids: vec!["1020".to_string()],
+            negative_tracking_ids: vec![],
+            filters: None,
+            limit: 2,
+        })
+        .await
+        .unwrap();
+    let mut recommended_ids = recommended
+        .iter()
+        .map(|result| result.chunk.tracking_id.clone().flatten().unwrap())
+        .collect::<Vec<String>>();
+    recommended_ids.sort();
+    assert_eq!(recommended_ids, vec!["1011", "1021"]);
+
+    let groups = backend
+        .group_search(&GroupSearchPayload {
+            query: "search".to_string(),
+            search_type: SearchMethod::Fulltext,
+            filters: None,
+            page: 1,
+            page_size: 10,
+            group_size: 1,
+            get_total_pages: true,
+        })
+        .await
+        .unwrap();
+    let story_ids = groups
+        .results
+        .iter()
+        .map(|result| result.group.tracking_id.clone().unwrap())
+        .collect::<Vec<String>>();
+    assert_eq!(story_ids, vec!["1010", "1020"]);
+    assert!(groups.results.iter().all(|result| result.chunks.len() == 1));
+    assert_eq!(groups.total_pages, Some(1));
+}
+
+#[actix_web::test]
+async fn replies_come_with_their_parent_comment() {
+    let resp = search("q=index+thread%3A1001&post_type=comment").await;
+    assert_eq!(resp["chunks"][0]["chunk"]["metadata"]["id"], 1002);
+    assert_eq!(resp["chunks"][0].get("parent"), None);
+    assert_eq!(
+        resp["chunks"][1]["parent"],
+        json!({
+            "id": 1002,
+            "by": "lindy",
+            "excerpt": "The tokenizer section is the best explanation of inverted indexes I have read."
+        })
+    );
+
+    let config = memory_config();
+    let backend: Arc<dyn SearchBackend> = Arc::new(backend());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(web::Data::from(backend))
+            .app_data(web::Data::new(config))
+            .service(page_handler::homepage),
+    )
+    .await;
+    let body = call_and_read_body(
+        &app,
+        TestRequest::get()
+            .uri("/?q=memory+mapped&post_type=comment")
+            .to_request(),
+    )
+    .await;
+    // The template's line breaks don't matter in HTML.
+    let body = String::from_utf8(body.to_vec())
+        .unwrap()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    assert!(body.contains(r#"href="/item/1002""#));
+    assert!(body.contains("The tokenizer section is the best explanation"));
+    assert!(body.contains(r#"href="/item/1001" >Writing a search engine in Rust</a"#));
+    assert!(
+        body.contains(r#"<a class="hover:underline" href="/item/1001#1003">view in thread</a>"#)
+    );
+}
+
+#[actix_web::test]
+async fn flag_filters_are_applied_before_paging() {
+    let mut top_level = search_ids("q=the+is%3Atop-level").await;
+    top_level.sort();
+    assert_eq!(top_level, vec![1002, 1004, 1011]);
+    assert_eq!(
+        search_ids("q=the+is%3Atop-level&page=2&page_size=2")
+            .await
+            .len(),
+        1
+    );
+    assert_eq!(
+        search_ids("q=search+-has%3Aurl+has%3Atext+type%3Astory").await,
+        vec![1020]
+    );
+}
+
+#[actix_web::test]
+async fn sorted_chunks_and_counts_need_no_query() {
+    let backend = backend();
+    let stories = CustomChunkFilter::tagged(&["story".to_string()]);
+    let ids = |chunks: Vec<ScoreChunkMetadata>| {
+        chunks
+            .iter()
+            .map(|chunk| chunk.chunk.tracking_id.clone().flatten().unwrap())
+            .collect::<Vec<String>>()
+    };
+    let top = sorted_chunks(&backend, stories.clone(), "num_value", SortOrder::Desc, 2);
+    let oldest = sorted_chunks(&backend, stories.clone(), "time_stamp", SortOrder::Asc, 1);
+    assert_eq!(ids(top.await.unwrap()), vec!["1001", "1020"]);
+    assert_eq!(ids(oldest.await.unwrap()), vec!["1030"]);
+    assert_eq!(count_chunks(&backend, stories).await.unwrap(), 4);
+
+    let payload = CountChunksPayload {
+        query: None,
+        search_type: CountSearchMethod::Fulltext,
+        filters: None,
+    };
+    assert_eq!(
+        serde_json::to_value(&payload).unwrap(),
+        json!({ "search_type": "fulltext" })
+    );
+}
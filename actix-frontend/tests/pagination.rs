@@ -0,0 +1,113 @@
+use hn_discovery_webserver::handlers::page_handler::{PageLink, Pager, SearchQueryParams};
+
+const MAX_PAGE: i64 = 1000;
+
+fn params(page: Option<i64>) -> SearchQueryParams {
+    SearchQueryParams {
+        q: Some("rust points>100".to_string()),
+        page,
+        page_size: None,
+        order_by: Some("date".to_string()),
+        search_type: None,
+        post_type: Some("show".to_string()),
+    }
+}
+
+fn numbers(pager: &Pager) -> Vec<Option<i64>> {
+    pager.pages.iter().map(|link| link.number).collect()
+}
+
+#[test]
+fn links_keep_filters_and_drop_page_one() {
+    let pager = Pager::new(&params(Some(2)), 30, MAX_PAGE, Some(3), 30);
+
+    assert_eq!(
+        pager.prev.as_deref(),
+        Some("/?q=rust+points%3E100&order_by=date&post_type=show")
+    );
+    assert_eq!(
+        pager.next.as_deref(),
+        Some("/?q=rust+points%3E100&page=3&order_by=date&post_type=show")
+    );
+    assert_eq!(numbers(&pager), vec![Some(1), Some(2), Some(3)]);
+    assert_eq!(
+        pager.pages[1],
+        PageLink {
+            number: Some(2),
+            url: Some("/?q=rust+points%3E100&page=2&order_by=date&post_type=show".to_string()),
+            current: true,
+        }
+    );
+}
+
+#[test]
+fn far_pages_collapse_into_gaps() {
+    let pager = Pager::new(&params(Some(10)), 30, MAX_PAGE, Some(20), 30);
+
+    assert_eq!(
+        numbers(&pager),
+        vec![
+            Some(1),
+            None,
+            Some(8),
+            Some(9),
+            Some(10),
+            Some(11),
+            Some(12),
+            None,
+            Some(20)
+        ]
+    );
+}
+
+#[test]
+fn first_and_last_pages_have_no_prev_or_next() {
+    let first = Pager::new(&params(None), 30, MAX_PAGE, Some(4), 30);
+    assert_eq!(first.prev, None);
+    assert!(first.next.is_some());
+
+    let last = Pager::new(&params(Some(4)), 30, MAX_PAGE, Some(4), 30);
+    assert!(last.prev.is_some());
+    assert_eq!(last.next, None);
+
+    let single = Pager::new(&params(None), 30, MAX_PAGE, Some(1), 12);
+    assert_eq!(
+        single,
+        Pager {
+            prev: None,
+            next: None,
+            pages: vec![]
+        }
+    );
+}
+
+#[test]
+fn without_total_pages_a_full_page_has_a_next_page() {
+    let full = Pager::new(&params(Some(2)), 30, MAX_PAGE, None, 30);
+    assert!(full.prev.is_some());
+    assert!(full.next.is_some());
+    assert!(full.pages.is_empty());
+
+    let partial = Pager::new(&params(Some(2)), 30, MAX_PAGE, None, 7);
+    assert_eq!(partial.next, None);
+}
+
+#[test]
+fn no_link_goes_past_the_max_page() {
+    let pager = Pager::new(&params(Some(99)), 30, 100, Some(5_000_000), 30);
+    assert_eq!(
+        numbers(&pager),
+        vec![Some(1), None, Some(97), Some(98), Some(99), Some(100)]
+    );
+    assert_eq!(
+        pager.next.as_deref(),
+        Some("/?q=rust+points%3E100&page=100&order_by=date&post_type=show")
+    );
+
+    let last = Pager::new(&params(Some(100)), 30, 100, Some(5_000_000), 30);
+    assert_eq!(last.next, None);
+    assert_eq!(numbers(&last).last(), Some(&Some(100)));
+
+    let unknown_total = Pager::new(&params(Some(100)), 30, 100, None, 30);
+    assert_eq!(unknown_total.next, None);
+}
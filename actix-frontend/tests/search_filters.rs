@@ -0,0 +1,424 @@
+use hn_discovery_webserver::{
+    handlers::search_handler::parse_search_payload_params,
+    query::{diagnostics::DiagnosticKind, parse},
+};
+use proptest::prelude::*;
+use serde_json::{json, Value};
+
+/// Cleaned query, the `filters` sent to Trieve and the kinds of diagnostics reported.
+fn lowered(query: &str) -> (String, Value, Vec<DiagnosticKind>) {
+    let lowered = parse_search_payload_params(query.to_string());
+    (
+        lowered.cleaned_query.clone(),
+        serde_json::to_value(lowered.chunk_filter()).unwrap(),
+        lowered
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.kind)
+            .collect(),
+    )
+}
+
+fn filters(must: Value, must_not: Value) -> Value {
+    json!({ "jsonb_prefilter": false, "must": must, "must_not": must_not })
+}
+
+#[test]
+fn author_and_site_filters() {
+    let catalogue = [
+        (
+            "rust by:pg",
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["pg"] }]),
+                json!([]),
+            ),
+            vec![],
+        ),
+        // Authors have to be at least two characters long...
+        (
+            "rust by:p",
+            filters(json!([]), json!([])),
+            vec![DiagnosticKind::InvalidValue],
+        ),
+        // ...unless they are excluded.
+        (
+            "rust by:-p",
+            filters(
+                json!([]),
+                json!([{ "field": "tag_set", "match_any": ["p"] }]),
+            ),
+            vec![],
+        ),
+        (
+            "-by:pg rust",
+            filters(
+                json!([]),
+                json!([{ "field": "tag_set", "match_any": ["pg"] }]),
+            ),
+            vec![],
+        ),
+        // Links show hosts without `www.`, so a bare host matches both.
+        (
+            "rust site:example.org",
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["example.org", "www.example.org"] }]),
+                json!([]),
+            ),
+            vec![],
+        ),
+        (
+            "rust site:www.example.org",
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["www.example.org"] }]),
+                json!([]),
+            ),
+            vec![],
+        ),
+        (
+            "rust site:-github.com",
+            filters(
+                json!([]),
+                json!([{ "field": "tag_set", "match_any": ["github.com", "www.github.com"] }]),
+            ),
+            vec![],
+        ),
+        (
+            "rust site:-",
+            filters(json!([]), json!([])),
+            vec![DiagnosticKind::InvalidValue],
+        ),
+        // GitHub links are also tagged with the owner of the repository...
+        (
+            "rust site:github.com/marlow",
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["github.com/marlow"] }]),
+                json!([]),
+            ),
+            vec![],
+        ),
+        // ...which is the only path a site can have.
+        (
+            "rust site:example.org/blog",
+            filters(json!([]), json!([])),
+            vec![DiagnosticKind::InvalidValue],
+        ),
+        (
+            "rust by:pg by:sama site:-x.com",
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["pg", "sama"] }]),
+                json!([{ "field": "tag_set", "match_any": ["x.com", "www.x.com"] }]),
+            ),
+            vec![],
+        ),
+    ];
+
+    for (query, expected, diagnostics) in catalogue {
+        assert_eq!(
+            lowered(query),
+            ("rust".to_string(), expected, diagnostics),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn type_and_thread_filters() {
+    let catalogue = [
+        (
+            "rust type:show,ask",
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["show", "ask"] }]),
+                json!([]),
+            ),
+        ),
+        (
+            "rust -type:job",
+            filters(
+                json!([]),
+                json!([{ "field": "tag_set", "match_any": ["job"] }]),
+            ),
+        ),
+        (
+            "rust thread:123",
+            filters(
+                json!([{ "field": "metadata.top_parent_id", "range": { "gte": 123, "lte": 123 } }]),
+                json!([]),
+            ),
+        ),
+        (
+            "rust story:123",
+            filters(
+                json!([{ "field": "metadata.top_parent_id", "range": { "gte": 123, "lte": 123 } }]),
+                json!([]),
+            ),
+        ),
+        (
+            "rust has:url",
+            filters(
+                json!([{ "field": "tag_set", "match_all": ["has:url"] }]),
+                json!([]),
+            ),
+        ),
+        (
+            "rust -has:url -has:text",
+            filters(
+                json!([]),
+                json!([{ "field": "tag_set", "match_any": ["has:url", "has:text"] }]),
+            ),
+        ),
+        (
+            "rust is:top-level",
+            filters(
+                json!([{ "field": "tag_set", "match_all": ["is:top-level"] }]),
+                json!([]),
+            ),
+        ),
+        (
+            "rust (is:top-level OR by:pg)",
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["is:top-level", "pg"] }]),
+                json!([]),
+            ),
+        ),
+    ];
+
+    for (query, expected) in catalogue {
+        assert_eq!(
+            lowered(query),
+            ("rust".to_string(), expected, vec![]),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn range_filters() {
+    let catalogue = [
+        (
+            "rust points>100 points<=500",
+            "num_value",
+            json!({ "gt": 100, "lte": 500 }),
+        ),
+        ("rust points:100..", "num_value", json!({ "gte": 100 })),
+        (
+            "rust points=100",
+            "num_value",
+            json!({ "gte": 100, "lte": 100 }),
+        ),
+        (
+            "rust comments:10..20",
+            "metadata.descendants",
+            json!({ "gte": 10, "lte": 20 }),
+        ),
+        // 2024-01-01T00:00:00Z until 2024-02-01T00:00:00Z
+        (
+            "rust date:2024-01",
+            "time_stamp",
+            json!({ "gte": 1704067200, "lt": 1706745600 }),
+        ),
+        (
+            "rust date>2024-01-31",
+            "time_stamp",
+            json!({ "gte": 1706745600 }),
+        ),
+        (
+            "rust year:2024",
+            "time_stamp",
+            json!({ "gte": 1704067200, "lt": 1735689600 }),
+        ),
+    ];
+
+    for (query, field, range) in catalogue {
+        assert_eq!(
+            lowered(query),
+            (
+                "rust".to_string(),
+                filters(json!([{ "field": field, "range": range }]), json!([])),
+                vec![]
+            ),
+            "{}",
+            query
+        );
+    }
+
+    assert_eq!(
+        lowered("rust points>500 points<100"),
+        (
+            "rust".to_string(),
+            filters(
+                json!([{ "field": "num_value", "range": { "gt": 500, "lt": 100 } }]),
+                json!([])
+            ),
+            vec![DiagnosticKind::ConflictingRange]
+        )
+    );
+}
+
+#[test]
+fn text_filters() {
+    assert_eq!(
+        lowered("rust title:\"rust async\""),
+        (
+            "rust \"rust async\"".to_string(),
+            filters(
+                json!([{ "field": "metadata.title", "match_all": ["rust async"] }]),
+                json!([])
+            ),
+            vec![]
+        )
+    );
+    assert_eq!(
+        lowered("rust -url:medium.com"),
+        (
+            "rust".to_string(),
+            filters(
+                json!([]),
+                json!([{ "field": "metadata.url", "match_any": ["medium.com"] }])
+            ),
+            vec![]
+        )
+    );
+}
+
+#[test]
+fn groups() {
+    assert_eq!(
+        lowered("(by:pg OR by:sama) rust"),
+        (
+            "rust".to_string(),
+            filters(
+                json!([{ "field": "tag_set", "match_any": ["pg", "sama"] }]),
+                json!([])
+            ),
+            vec![]
+        )
+    );
+    assert_eq!(
+        lowered("rust (title:go OR points>100)"),
+        (
+            "rust go".to_string(),
+            json!({
+                "jsonb_prefilter": false,
+                "must": [],
+                "must_not": [],
+                "should": [
+                    { "field": "metadata.title", "match_all": ["go"] },
+                    { "field": "num_value", "range": { "gt": 100 } },
+                ],
+            }),
+            vec![]
+        )
+    );
+    assert_eq!(
+        lowered("rust -(by:pg OR site:x.com)"),
+        (
+            "rust".to_string(),
+            filters(
+                json!([]),
+                json!([
+                    { "field": "tag_set", "match_any": ["pg"] },
+                    { "field": "tag_set", "match_any": ["x.com", "www.x.com"] },
+                ])
+            ),
+            vec![]
+        )
+    );
+}
+
+#[test]
+fn text_which_is_not_a_filter() {
+    let catalogue = [
+        ("by:pg", "", vec![DiagnosticKind::EmptyQuery]),
+        (
+            "rust autor:pg",
+            "rust autor:pg",
+            vec![DiagnosticKind::UnknownOperator],
+        ),
+        (
+            "rust by>pg",
+            "rust by>pg",
+            vec![DiagnosticKind::UnknownOperator],
+        ),
+        (
+            "https://news.ycombinator.com",
+            "https://news.ycombinator.com",
+            vec![],
+        ),
+        ("meet at 10:30", "meet at 10:30", vec![]),
+        (
+            "rust -go \"async await\"",
+            "rust -go \"async await\"",
+            vec![],
+        ),
+    ];
+
+    for (query, cleaned, diagnostics) in catalogue {
+        let (cleaned_query, _, kinds) = lowered(query);
+        assert_eq!(
+            (cleaned_query.as_str(), kinds),
+            (cleaned, diagnostics),
+            "{}",
+            query
+        );
+    }
+}
+
+/// Query built from plain words and valid filters, along with the words allowed to end up
+/// in the cleaned query.
+fn words_and_filters() -> impl Strategy<Value = (String, Vec<String>)> {
+    let part = prop_oneof![
+        "[a-z]{1,8}".prop_map(|word| (word.clone(), vec![word])),
+        "[a-z]{1,8}".prop_map(|word| (format!("title:{}", word), vec![word])),
+        "[a-z]{1,8}".prop_map(|word| (format!("text:{}", word), vec![word])),
+        "-?(by|site):[a-z0-9.]{2,10}".prop_map(|filter| (filter, vec![])),
+        "-?type:(story|comment|show,ask)".prop_map(|filter| (filter, vec![])),
+        "(points|comments)(>|>=|<|<=|:)[0-9]{1,5}".prop_map(|filter| (filter, vec![])),
+        "(points|comments):[0-9]{1,3}\\.\\.[0-9]{1,3}".prop_map(|filter| (filter, vec![])),
+        "date(>|<|:)(20[0-2][0-9]|20[0-2][0-9]-0[1-9]|[1-9][0-9]?[hdwy])"
+            .prop_map(|filter| (filter, vec![])),
+        "(thread|story):[0-9]{1,8}".prop_map(|filter| (filter, vec![])),
+        "-?(has:url|has:text|url:[a-z.]{1,8})".prop_map(|filter| (filter, vec![])),
+    ];
+    prop::collection::vec(part, 1..8).prop_map(|parts| {
+        let (query, words): (Vec<String>, Vec<Vec<String>>) = parts.into_iter().unzip();
+        (query.join(" "), words.concat())
+    })
+}
+
+proptest! {
+    #[test]
+    fn parsing_never_panics(query in "\\PC{0,60}") {
+        let _ = parse_search_payload_params(query);
+    }
+
+    #[test]
+    fn parsing_syntax_heavy_input_never_panics(query in "[a-z0-9:<>=.,()\" -]{0,60}") {
+        let _ = parse_search_payload_params(query);
+    }
+
+    #[test]
+    fn filters_never_leak_into_cleaned_query((query, words) in words_and_filters()) {
+        let lowered = parse_search_payload_params(query.clone());
+
+        prop_assert!(
+            lowered.diagnostics.iter().all(|diagnostic| matches!(
+                diagnostic.kind,
+                DiagnosticKind::EmptyQuery | DiagnosticKind::ConflictingRange
+            )),
+            "{}: {:?}", query, lowered.diagnostics
+        );
+        for word in lowered.cleaned_query.split_whitespace() {
+            prop_assert!(words.iter().any(|allowed| allowed == word), "{}: {}", query, word);
+        }
+    }
+
+    #[test]
+    fn diagnostics_point_into_the_query(query in "[a-z0-9:<>=.,()\" -]{0,60}") {
+        for diagnostic in parse(&query).diagnostics {
+            prop_assert_eq!(diagnostic.span.slice(&query), diagnostic.fragment.as_str());
+            prop_assert!(diagnostic.span.start <= diagnostic.span.end && diagnostic.span.end <= query.len());
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use hn_discovery_webserver::{
+    config::SearchConfig,
+    errors::SearchError,
+    handlers::{
+        page_handler::SearchQueryParams,
+        search_request::{OrderBy, PostType, SearchRequest, SearchType},
+    },
+};
+
+fn params() -> SearchQueryParams {
+    SearchQueryParams {
+        q: Some("rust".to_string()),
+        page: None,
+        page_size: None,
+        order_by: None,
+        search_type: None,
+        post_type: None,
+    }
+}
+
+#[test]
+fn missing_and_empty_params_take_defaults() {
+    let defaults = SearchRequest {
+        q: "rust".to_string(),
+        page: 1,
+        page_size: 30,
+        order_by: OrderBy::Relevance,
+        search_type: SearchType::Fulltext,
+        post_type: PostType::All,
+    };
+    assert_eq!(
+        SearchRequest::new(&params(), &SearchConfig::default()),
+        Ok(defaults.clone())
+    );
+    assert_eq!(
+        SearchRequest::new(
+            &SearchQueryParams {
+                order_by: Some("".to_string()),
+                search_type: Some(" ".to_string()),
+                post_type: Some("".to_string()),
+                ..params()
+            },
+            &SearchConfig::default()
+        ),
+        Ok(defaults)
+    );
+}
+
+#[test]
+fn dropdown_values_are_parsed() {
+    let request = SearchRequest::new(
+        &SearchQueryParams {
+            order_by: Some("comments".to_string()),
+            search_type: Some("keyword".to_string()),
+            post_type: Some("show".to_string()),
+            ..params()
+        },
+        &SearchConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(request.order_by, OrderBy::Comments);
+    assert_eq!(request.search_type, SearchType::Keyword);
+    assert_eq!(request.post_type, PostType::Show);
+}
+
+#[test]
+fn large_page_sizes_are_clamped() {
+    let request = SearchRequest::new(
+        &SearchQueryParams {
+            page_size: Some(100_000),
+            ..params()
+        },
+        &SearchConfig::default(),
+    )
+    .unwrap();
+    assert_eq!(request.page_size, 100);
+}
+
+#[test]
+fn invalid_params_are_rejected() {
+    for invalid in [
+        SearchQueryParams {
+            page: Some(-3),
+            ..params()
+        },
+        SearchQueryParams {
+            page: Some(0),
+            ..params()
+        },
+        SearchQueryParams {
+            page: Some(1001),
+            ..params()
+        },
+        SearchQueryParams {
+            page_size: Some(0),
+            ..params()
+        },
+        SearchQueryParams {
+            order_by: Some("karma".to_string()),
+            ..params()
+        },
+        SearchQueryParams {
+            search_type: Some("vibes".to_string()),
+            ..params()
+        },
+        SearchQueryParams {
+            post_type: Some("ask".to_string()),
+            ..params()
+        },
+    ] {
+        assert!(
+            matches!(
+                SearchRequest::new(&invalid, &SearchConfig::default()),
+                Err(SearchError::InvalidParams(_))
+            ),
+            "{:?}",
+            invalid
+        );
+    }
+}
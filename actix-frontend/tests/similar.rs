@@ -0,0 +1,186 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body, TestRequest},
+    web, App,
+};
+use common::{backend, memory_config, memory_config_with};
+use hn_discovery_webserver::{
+    backend::{
+        memory::{item_to_chunk, HnItem},
+        InMemoryBackend, SearchBackend,
+    },
+    handlers::{
+        search_handler::{cache_period_start, get_recommendations},
+        search_request::{PostType, SimilarRequest},
+        similar_handler::{self, SimilarQueryParams},
+    },
+    query::{lower::lower_at, parse},
+    templates,
+};
+use std::sync::Arc;
+
+#[test]
+
+fn similar_requests_check_their_params() {
+    let params = |negative: &str, post_type: &str| SimilarQueryParams {
+        q: None,
+        post_type: Some(post_type.to_string()),
+        negative: Some(negative.to_string()),
+    };
+    let config = memory_config().search;
+
+    let request =
+        SimilarRequest::new(1020, &params("1021, 1011,,1020,1021", "comment"), &config).unwrap();
+    assert_eq!(request.negative, vec![1021, 1011]);
+    assert_eq!(request.post_type, PostType::Comment);
+    assert!(SimilarRequest::new(1020, &params("1021,pg", ""), &config).is_err());
+    assert!(SimilarRequest::new(1020, &params("", "stories"), &config).is_err());
+    let too_many = (1..=config.max_negative as i64 + 1)
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    assert!(SimilarRequest::new(1020, &params(&too_many, ""), &config).is_err());
+
+    assert_eq!(params("", "").url(1020), "/similar/1020");
+    assert_eq!(
+        params("1021,1011", "story").url(1020),
+        "/similar/1020?post_type=story&negative=1021%2C1011"
+    );
+}
+
+#[actix_web::test]
+async fn recommendations_honor_filters_and_negative_examples() {
+    let backend = backend();
+    let config = memory_config();
+    let similar = |q: &str, post_type: PostType, negative: Vec<i64>| {
+        let request = SimilarRequest {
+            id: 1020,
+            q: q.to_string(),
+            post_type,
+            negative,
+            limit: 10,
+        };
+        let (backend, config) = (&backend, &config);
+        async move {
+            get_recommendations(backend, config, &request)
+                .await
+                .unwrap()
+                .chunks
+                .iter()
+                .map(|result| result.chunk.tracking_id.clone().flatten().unwrap())
+                .collect::<Vec<String>>()
+        }
+    };
+
+    assert_eq!(
+        similar("", PostType::All, vec![]).await,
+        vec!["1011", "1021", "1001", "1010", "1040"]
+    );
+    assert_eq!(
+        similar("", PostType::Comment, vec![]).await,
+        vec!["1011", "1021"]
+    );
+    assert_eq!(
+        similar("by:lindy", PostType::All, vec![]).await,
+        vec!["1021"]
+    );
+    assert_eq!(
+        similar("", PostType::All, vec![1021]).await,
+        vec!["1011", "1010", "1040"]
+    );
+}
+
+#[actix_web::test]
+async fn recommendations_count_relative_dates_like_searches() {
+    let config = memory_config_with(&[("CACHE_TTL_SECS", "3600")]);
+    let request = SimilarRequest {
+        id: 1020,
+        q: "date>7d".to_string(),
+        post_type: PostType::All,
+        negative: vec![],
+        limit: 10,
+    };
+    let resp = get_recommendations(&backend(), &config, &request)
+        .await
+        .unwrap();
+
+    let now = cache_period_start(chrono::Utc::now().timestamp(), config.cache.ttl_secs);
+    assert_eq!(
+        serde_json::to_value(resp.parsed_query.unwrap().filters).unwrap(),
+        serde_json::to_value(lower_at(&parse("date>7d"), now).chunk_filter()).unwrap()
+    );
+}
+
+#[actix_web::test]
+async fn similar_pages_link_to_less_like_this() {
+    let config = memory_config();
+    let backend: Arc<dyn SearchBackend> = Arc::new(backend());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(web::Data::from(backend))
+            .app_data(web::Data::new(config))
+            .service(similar_handler::similar),
+    )
+    .await;
+    let get = |uri: &str| TestRequest::get().uri(uri).to_request();
+
+    let resp = call_service(&app, get("/similar/1020?post_type=comment&negative=1004")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("Ask HN: What are you using for full-text search?"));
+    assert!(body.contains("Unlike 1 item(s)."));
+    assert!(body.contains("negative=1004%2C1011"));
+    assert!(body.contains("negative=1004%2C1021"));
+
+    let resp = call_service(&app, get("/similar/42")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = call_service(&app, get("/similar/1020?negative=pg")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn less_like_this_links_stay_with_their_result() {
+    let config = memory_config_with(&[("MAX_NEGATIVE", "2")]);
+    let items = serde_json::from_str::<Vec<HnItem>>(
+        &std::fs::read_to_string("fixtures/hn_items.json").unwrap(),
+    )
+    .unwrap();
+    let chunks = items
+        .iter()
+        .map(|item| {
+            let mut chunk = item_to_chunk(item, None);
+            if item.id == 1011 {
+                chunk.tracking_id = None;
+            }
+            chunk
+        })
+        .collect();
+    let backend: Arc<dyn SearchBackend> = Arc::new(InMemoryBackend::new(chunks));
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(web::Data::from(backend))
+            .app_data(web::Data::new(config))
+            .service(similar_handler::similar),
+    )
+    .await;
+    let get = |uri: &str| TestRequest::get().uri(uri).to_request();
+
+    let resp = call_service(&app, get("/similar/1020?post_type=comment&negative=1004")).await;
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    let (result_1011, result_1021) = (body.find("#1011").unwrap(), body.find("#1021").unwrap());
+    let link_1021 = body.find("negative=1004%2C1021").unwrap();
+    assert_eq!(body.matches("less like this").count(), 1);
+    assert!(result_1021 < link_1021 && (result_1011 < result_1021 || link_1021 < result_1011));
+
+    let resp = call_service(&app, get("/similar/1020?negative=1004%2C1021")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("#1011"));
+    assert!(!body.contains("less like this"));
+    let resp = call_service(&app, get("/similar/1020?negative=1004%2C1021%2C1011")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,115 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body, TestRequest},
+    web, App,
+};
+use common::{backend, memory_config};
+use hn_discovery_webserver::{
+    backend::{InMemoryBackend, SearchBackend},
+    handlers::site_handler,
+    site::{get_site, is_site, SitePage, SubmitterCount, YearCount},
+    templates,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+#[actix_web::test]
+async fn site_pages_count_stories_by_year_and_submitter() {
+    let item = |id: i64, item_type: &str, by: &str, time: i64, score: i64| {
+        common::item(json!({
+            "id": id,
+            "type": item_type,
+            "by": by,
+            "time": time,
+            "url": "https://example.org/post",
+            "score": score,
+        }))
+    };
+    let backend = InMemoryBackend::new(vec![
+        item(1, "story", "ada", 1577836800, 10),
+        item(2, "story", "ada", 1640995200, 5),
+        item(3, "story", "bob", 1641081600, 30),
+        item(4, "comment", "cy", 1641081600, 0),
+    ]);
+    let page = get_site(&backend, "example.org").await.unwrap().unwrap();
+
+    assert_eq!((page.num_stories, page.total_points), (3, 45));
+    let year = |year: i32, count: usize, percent: usize| YearCount {
+        year,
+        count,
+        percent,
+    };
+    assert_eq!(
+        page.timeline,
+        vec![year(2020, 1, 50), year(2021, 0, 0), year(2022, 2, 100)]
+    );
+    let submitter = |by: &str, count: usize, points: i64| SubmitterCount {
+        by: by.to_string(),
+        count,
+        points,
+    };
+    assert_eq!(
+        page.top_submitters,
+        vec![submitter("ada", 2, 15), submitter("bob", 1, 30)]
+    );
+    let top_story_ids = page
+        .top_stories
+        .iter()
+        .map(|story| story.chunk.tracking_id.clone().flatten().unwrap())
+        .collect::<Vec<String>>();
+    assert_eq!(top_story_ids, vec!["3", "1", "2"]);
+    assert!(!page.truncated);
+}
+
+#[actix_web::test]
+async fn sites_match_www_hosts_and_github_owners() {
+    let story_ids = |page: Option<SitePage>| {
+        page.unwrap()
+            .top_stories
+            .iter()
+            .map(|story| story.chunk.tracking_id.clone().flatten().unwrap())
+            .collect::<Vec<String>>()
+    };
+    let backend = backend();
+    assert_eq!(
+        story_ids(get_site(&backend, "example.org").await.unwrap()),
+        vec!["1030"]
+    );
+    assert_eq!(
+        story_ids(get_site(&backend, "github.com/marlow").await.unwrap()),
+        vec!["1010"]
+    );
+    assert!(get_site(&backend, "example.com").await.unwrap().is_none());
+
+    assert!(is_site("news.example.com") && is_site("github.com/marlow"));
+    assert!(!is_site("example.org/blog") && !is_site("x") && !is_site("a b.com"));
+}
+
+#[actix_web::test]
+async fn site_pages_render_the_stories() {
+    let config = memory_config();
+    let backend: Arc<dyn SearchBackend> = Arc::new(backend());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(web::Data::from(backend))
+            .app_data(web::Data::new(config))
+            .service(site_handler::site),
+    )
+    .await;
+    let get = |uri: &str| TestRequest::get().uri(uri).to_request();
+
+    let resp = call_service(&app, get("/site/github.com/marlow")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("Show HN: A no-JS frontend for Hacker News search"));
+    assert!(body.contains(r#"href="/user/marlow""#));
+    assert!(body.contains("95 points"));
+
+    let resp = call_service(&app, get("/site/example.com")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = call_service(&app, get("/site/example.org%2Fblog")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use actix_web::{
+    http::StatusCode,
+    test::{call_service, init_service, read_body, TestRequest},
+    web, App,
+};
+use common::{backend, memory_config};
+use hn_discovery_webserver::{
+    backend::{InMemoryBackend, SearchBackend},
+    handlers::user_handler,
+    profile::{get_profile, is_username, SiteCount},
+    templates,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+#[actix_web::test]
+async fn profiles_sum_points_and_list_items() {
+    let profile = get_profile(&backend(), "harbor").await.unwrap().unwrap();
+    // Their poll isn't a story, as in a `post_type=story` search.
+    assert_eq!((profile.num_stories, profile.num_comments), (1, 0));
+    assert_eq!(profile.total_points, 58);
+    assert_eq!(
+        (profile.first_active, profile.last_active),
+        (1609459200, 1706745600)
+    );
+    assert_eq!(
+        profile.top_sites,
+        vec![SiteCount {
+            site: "example.org".to_string(),
+            count: 1
+        }]
+    );
+    let top_story_ids = profile
+        .top_stories
+        .iter()
+        .map(|story| story.chunk.tracking_id.clone().flatten().unwrap())
+        .collect::<Vec<String>>();
+    assert_eq!(top_story_ids, vec!["1030"]);
+
+    let profile = get_profile(&backend(), "marlow").await.unwrap().unwrap();
+    let recent_comments = profile
+        .recent_comments
+        .iter()
+        .map(|comment| {
+            (
+                comment.chunk.tracking_id.clone().flatten().unwrap(),
+                comment.parent.as_ref().map(|parent| parent.id),
+            )
+        })
+        .collect::<Vec<(String, Option<i64>)>>();
+    assert_eq!(recent_comments, vec![("1012".to_string(), Some(1011))]);
+
+    assert!(get_profile(&backend(), "nobody").await.unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn profiles_skip_items_which_only_share_the_tag() {
+    let item = |id: i64, by: &str, title: &str| {
+        common::item(json!({
+            "id": id,
+            "type": "story",
+            "by": by,
+            "time": id,
+            "title": title,
+            "score": 10,
+        }))
+    };
+    let backend = InMemoryBackend::new(vec![
+        item(1, "show", "Ask HN: Hi"),
+        item(2, "pg", "Show HN: Hi"),
+    ]);
+    let profile = get_profile(&backend, "show").await.unwrap().unwrap();
+    assert_eq!(profile.num_stories, 1);
+    assert_eq!(profile.total_points, 10);
+    assert_eq!(profile.top_stories.len(), 1);
+    assert!(!profile.truncated);
+
+    let backend = InMemoryBackend::new(vec![item(2, "pg", "Show HN: Hi")]);
+    assert!(get_profile(&backend, "show").await.unwrap().is_none());
+    assert!(is_username("dang") && is_username("a_b-1"));
+    assert!(!is_username("x") && !is_username("by:dang") && !is_username("a b"));
+}
+
+#[actix_web::test]
+async fn user_pages_render_the_profile() {
+    let config = memory_config();
+    let backend: Arc<dyn SearchBackend> = Arc::new(backend());
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(templates(&config)))
+            .app_data(web::Data::from(backend))
+            .app_data(web::Data::new(config))
+            .service(user_handler::user),
+    )
+    .await;
+    let get = |uri: &str| TestRequest::get().uri(uri).to_request();
+
+    let resp = call_service(&app, get("/user/ferris")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("412 points"));
+    assert!(body.contains("Writing a search engine in Rust"));
+    assert!(body.contains(r#"href="/item/1001#1003""#));
+    assert!(body.contains(r#"value="by:ferris ""#));
+
+    let resp = call_service(&app, get("/user/nobody")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = call_service(&app, get("/user/x")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
@@ -88,6 +88,14 @@ def deserialize_to_dict(item):
         if row.get("title") and row.get("title").startswith("Ask HN:"):
             tags.append("ask")
 
+        # Flags the search filters on, since Trieve can't compare fields or check emptiness
+        if row.get("type", "") == "comment" and row.get("parent") == row.get("top_parent_id"):
+            tags.append("is:top-level")
+
+        for field in ["title", "text", "url"]:
+            if row.get(field):
+                tags.append("has:" + field)
+
         html = ""
 
         if row.get("title"):
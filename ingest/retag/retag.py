@@ -0,0 +1,63 @@
+import os
+import requests
+
+# Adds the `has:`/`is:` tags bulk_ingest gives new chunks to the chunks ingested before it
+# did, since the search filters on them. Safe to run again, chunks already tagged are skipped.
+
+api_key = os.getenv("API_KEY")
+dataset_id = os.getenv("DATASET_ID")
+api_url = os.getenv("API_BASE_URL")  # "https://hackernews.withtrieve.com/api"
+page_size = int(os.getenv("PAGE_SIZE", 1000))
+
+headers = {
+    "Content-Type": "application/json",
+    "TR-Dataset": dataset_id,
+    "Authorization": api_key,
+}
+
+
+def flag_tags(metadata):
+    # Same flags as bulk_ingest
+    tags = []
+    if metadata.get("type", "") == "comment" and metadata.get("parent") == metadata.get(
+        "top_parent_id"
+    ):
+        tags.append("is:top-level")
+
+    for field in ["title", "text", "url"]:
+        if metadata.get(field):
+            tags.append("has:" + field)
+    return tags
+
+
+offset_chunk_id = None
+num_retagged = 0
+while True:
+    body = {"page_size": page_size}
+    if offset_chunk_id:
+        body["offset_chunk_id"] = offset_chunk_id
+    resp = requests.post(f"{api_url}/chunks/scroll", headers=headers, json=body)
+    resp.raise_for_status()
+    page = resp.json()["chunks"]
+    # Pages start at their offset chunk, which ended the previous page
+    chunks = [chunk for chunk in page if chunk["id"] != offset_chunk_id]
+
+    for chunk in chunks:
+        tags = chunk.get("tag_set") or []
+        kept = [tag for tag in tags if not tag.startswith(("has:", "is:"))]
+        new_tags = kept + flag_tags(chunk.get("metadata") or {})
+        if sorted(new_tags) == sorted(tags):
+            continue
+        requests.put(
+            f"{api_url}/chunk",
+            headers=headers,
+            json={"chunk_id": chunk["id"], "tag_set": new_tags},
+        ).raise_for_status()
+        num_retagged += 1
+
+    print("scrolled", len(chunks), "retagged", num_retagged)
+    if not chunks or len(page) < page_size:
+        break
+    offset_chunk_id = chunks[-1]["id"]
+
+print("Retagged", num_retagged, "chunks")